@@ -0,0 +1,24 @@
+use std::{fs, io, path::PathBuf};
+
+use crate::error::{CaverResult, IntoCaverResult, WithContext};
+
+/// Directory holding the user's configuration files, e.g. `~/.config/caver`
+pub fn dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("caver"))
+}
+
+/// Parses the configuration file at `path` with `parse`, the defaults if there is none
+pub(crate) fn load<T: Default>(
+    path: Option<PathBuf>,
+    parse: impl FnOnce(&str) -> CaverResult<T>,
+) -> CaverResult<T> {
+    let Some(path) = path else {
+        return Ok(T::default());
+    };
+
+    match fs::read_to_string(&path) {
+        Ok(s) => parse(&s).context(path),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(T::default()),
+        Err(e) => Err(e).into_caver_result().context(path),
+    }
+}
@@ -0,0 +1,182 @@
+//! Json api over HTTP, shaped like the one of voidtools Everything's HTTP server
+//! so launcher plugins written for Everything work against caver.
+//!
+//! `GET /search?search=<query>&offset=<n>&count=<n>&sort=<column>&ascending=<0|1>`
+//! (`/?s=<query>&json=1` and the `s`, `q`, `o` and `c` short names are accepted too)
+//! answers with
+//! ```json
+//! {"totalResults": 1, "results": [{"type": "file", "name": "main.rs", "path": "C:\\caver\\src",
+//!   "size": "1024", "date_modified": "133540000000000000", "date_created": "133540000000000000"}]}
+//! ```
+//! where `path` is the parent directory and dates are FILETIMEs, like Everything does.
+//! Without `count`, the first [`DEFAULT_COUNT`] results are returned.
+//!
+//! `GET /stats` answers with the [`SourceStats`] of every disk, as `caver stats` prints them.
+//!
+//! There is no authentication, so only loopback addresses are accepted unless asked otherwise.
+
+use std::{collections::HashMap, net::ToSocketAddrs, path::PathBuf, sync::Arc, thread};
+
+use serde::Serialize;
+use tiny_http::{Header, Response, Server};
+use tracing::debug;
+
+use crate::{
+    error::{CaverError, CaverResult},
+    file::{index::SourceStats, File},
+};
+
+use super::{Daemon, SortKey};
+
+/// Results returned when a request doesn't set `count`
+pub const DEFAULT_COUNT: usize = 100;
+
+/// Requests answered at the same time
+pub const HTTP_THREADS: usize = 4;
+
+/// Listens on `addr`, which must be a loopback address unless `remote` is set
+pub fn bind(addr: &str, remote: bool) -> CaverResult<Server> {
+    let addrs = (addr.to_socket_addrs()).map_err(|e| CaverError::Http(e.to_string()))?;
+    if !remote {
+        if let Some(addr) = addrs.into_iter().find(|addr| !addr.ip().is_loopback()) {
+            return Err(CaverError::Http(format!(
+                "{addr} is reachable from other machines and the api has no authentication, \
+                 allow it explicitly to listen there"
+            )));
+        }
+    }
+    Server::http(addr).map_err(|e| CaverError::Http(e.to_string()))
+}
+
+/// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01
+const FILETIME_UNIX_OFFSET: i64 = 11_644_473_600;
+
+fn filetime(timestamp: Option<i64>) -> String {
+    timestamp
+        .map(|t| ((t + FILETIME_UNIX_OFFSET) as i128 * 10_000_000).to_string())
+        .unwrap_or_default()
+}
+
+#[derive(Serialize)]
+struct EverythingResult {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    name: String,
+    path: String,
+    size: String,
+    date_modified: String,
+    date_created: String,
+}
+
+impl EverythingResult {
+    fn new(file: &File, path: PathBuf) -> Self {
+        Self {
+            kind: if file.is_dir() { "folder" } else { "file" },
+            name: file.name.clone(),
+            path: path
+                .parent()
+                .map(|parent| parent.to_string_lossy().into_owned())
+                .unwrap_or_default(),
+            size: file.size.to_string(),
+            date_modified: filetime(file.modified),
+            date_created: filetime(file.created),
+        }
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct EverythingResults {
+    total_results: usize,
+    results: Vec<EverythingResult>,
+}
+
+#[derive(Serialize)]
+struct Stats<'a> {
+    disks: &'a [SourceStats],
+}
+
+fn error(status: u16, message: &str) -> (u16, String) {
+    (status, serde_json::json!({ "error": message }).to_string())
+}
+
+impl Daemon {
+    fn http_search(&self, params: &HashMap<String, String>) -> (u16, String) {
+        // Empty values are left to their default, like missing ones
+        let param = |names: &[&str]| {
+            (names.iter()).find_map(|name| params.get(*name).filter(|s| !s.is_empty()))
+        };
+
+        let query = param(&["search", "s", "q"]).map_or("", |s| s.as_str());
+
+        let Ok(offset) = param(&["offset", "o"]).map_or(Ok(0), |s| s.parse()) else {
+            return error(400, "invalid offset");
+        };
+        let Ok(count) = param(&["count", "c"]).map_or(Ok(DEFAULT_COUNT), |s| s.parse()) else {
+            return error(400, "invalid count");
+        };
+
+        let sort = match param(&["sort"]) {
+            Some(name) => match SortKey::from_name(name) {
+                Some(key) => {
+                    let ascending = param(&["ascending"]).is_none_or(|s| s != "0");
+                    Some((key, ascending))
+                }
+                None => return error(400, "invalid sort"),
+            },
+            None => None,
+        };
+
+        let (total_results, results) =
+            self.search(query, sort, offset, Some(count), EverythingResult::new);
+
+        let body = EverythingResults {
+            total_results,
+            results,
+        };
+        (200, serde_json::to_string(&body).unwrap())
+    }
+
+    fn http_stats(&self) -> (u16, String) {
+        let index = self.index.read().unwrap();
+        let stats = Stats {
+            disks: index.stats(),
+        };
+        (200, serde_json::to_string(&stats).unwrap())
+    }
+
+    /// Returns the status code and json body answering `url`
+    pub fn handle_http(&self, url: &str) -> (u16, String) {
+        let (path, query) = url.split_once('?').unwrap_or((url, ""));
+        let params = form_urlencoded::parse(query.as_bytes())
+            .into_owned()
+            .collect::<HashMap<_, _>>();
+
+        match path {
+            "/" | "/search" => self.http_search(&params),
+            "/stats" => self.http_stats(),
+            _ => error(404, "not found"),
+        }
+    }
+
+    /// Answers HTTP requests until the process is stopped, on [`HTTP_THREADS`] threads
+    pub fn serve_http(self: Arc<Self>, server: Server) {
+        let content_type = Header::from_bytes("Content-Type", "application/json").unwrap();
+
+        thread::scope(|scope| {
+            for _ in 0..HTTP_THREADS {
+                scope.spawn(|| {
+                    for request in server.incoming_requests() {
+                        let (status, body) = self.handle_http(request.url());
+                        let response = Response::from_string(body)
+                            .with_status_code(status)
+                            .with_header(content_type.clone());
+                        if let Err(e) = request.respond(response) {
+                            debug!("http response failed : {e}");
+                        }
+                    }
+                });
+            }
+        });
+    }
+}
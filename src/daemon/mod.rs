@@ -0,0 +1,339 @@
+pub mod http;
+pub mod protocol;
+#[cfg(test)]
+mod test;
+
+use std::{
+    cmp::Ordering,
+    env,
+    io::{self, BufReader, BufWriter},
+    path::PathBuf,
+    sync::{Arc, RwLock},
+    thread,
+    time::{Duration, Instant},
+};
+
+use interprocess::local_socket::{
+    prelude::*, GenericFilePath, GenericNamespaced, ListenerOptions, Name, RecvHalf, SendHalf,
+    Stream,
+};
+use tracing::{debug, error, info, warn};
+
+use crate::{
+    error::{CaverError, CaverResult},
+    file::{index::FileIndex, File},
+    output::ResultRecord,
+    search::SearchParams,
+};
+
+use protocol::{read_message, write_message, Request, Response, MAX_MESSAGE_LEN, MAX_REQUEST_LEN};
+
+/// Named after the user, abstract sockets and named pipes being shared by every user
+pub fn default_socket() -> String {
+    #[cfg(unix)]
+    let user = euid().to_string();
+    #[cfg(not(unix))]
+    let user = env::var("USERNAME").unwrap_or_default();
+    format!("caver-{user}.sock")
+}
+
+#[cfg(unix)]
+fn euid() -> u32 {
+    // SAFETY: geteuid has no preconditions and can't fail
+    unsafe { libc::geteuid() }
+}
+
+/// Whether the other side of `stream` runs as the same user as this process.
+/// The daemon would let others read files they have no access to, and a client would trust
+/// results served by someone else
+#[cfg(unix)]
+fn is_same_user(stream: &Stream) -> io::Result<bool> {
+    Ok(stream.peer_creds()?.euid() == Some(euid()))
+}
+
+/// The named pipe is only open to its owner, see [`listener_options`]
+#[cfg(not(unix))]
+fn is_same_user(_stream: &Stream) -> io::Result<bool> {
+    Ok(true)
+}
+
+/// Results asked at once by [`Client::search_all`], keeping each response well under [`MAX_MESSAGE_LEN`]
+pub const SEARCH_PAGE: usize = 10_000;
+
+/// How long a client waits for the daemon to answer
+pub const CLIENT_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A named pipe on windows, an abstract socket on linux, or a socket file in the temp directory
+pub fn socket_name(socket: &str) -> io::Result<Name<'static>> {
+    if GenericNamespaced::is_supported() {
+        socket.to_string().to_ns_name::<GenericNamespaced>()
+    } else {
+        env::temp_dir().join(socket).to_fs_name::<GenericFilePath>()
+    }
+}
+
+/// Only the user running the daemon can open its named pipe
+#[cfg(windows)]
+fn listener_options(name: Name<'_>) -> io::Result<ListenerOptions<'_>> {
+    use interprocess::os::windows::{
+        local_socket::ListenerOptionsExt, security_descriptor::SecurityDescriptor,
+    };
+    use widestring::u16cstr;
+
+    // Protected DACL granting everything to the owner, and nothing to anyone else
+    let sd = SecurityDescriptor::deserialize(u16cstr!("D:P(A;;GA;;;OW)"))?;
+    Ok(ListenerOptions::new().name(name).security_descriptor(sd))
+}
+
+/// Abstract sockets have no permissions, the peer is checked by [`is_same_user`] instead
+#[cfg(not(windows))]
+fn listener_options(name: Name<'_>) -> io::Result<ListenerOptions<'_>> {
+    Ok(ListenerOptions::new().name(name))
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortKey {
+    Name,
+    Path,
+    Extension,
+    Size,
+    DateCreated,
+    DateModified,
+    DateAccessed,
+}
+
+impl SortKey {
+    /// Uses the same names as Everything's `sort` parameter
+    pub fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "name" => Self::Name,
+            "path" => Self::Path,
+            "extension" => Self::Extension,
+            "size" => Self::Size,
+            "date_created" => Self::DateCreated,
+            "date_modified" => Self::DateModified,
+            "date_accessed" => Self::DateAccessed,
+            _ => return None,
+        })
+    }
+
+    pub fn compare(&self, a: &(&File, PathBuf), b: &(&File, PathBuf)) -> Ordering {
+        fn extension(name: &str) -> &str {
+            name.rsplit_once('.').map_or("", |(_, ext)| ext)
+        }
+
+        match self {
+            Self::Name => a.0.name.cmp(&b.0.name),
+            Self::Path => a.1.cmp(&b.1),
+            Self::Extension => extension(&a.0.name).cmp(extension(&b.0.name)),
+            Self::Size => a.0.size.cmp(&b.0.size),
+            Self::DateCreated => a.0.created.cmp(&b.0.created),
+            Self::DateModified => a.0.modified.cmp(&b.0.modified),
+            Self::DateAccessed => a.0.accessed.cmp(&b.0.accessed),
+        }
+    }
+}
+
+/// Keeps an index in memory to answer queries from other processes
+pub struct Daemon {
+    index: RwLock<FileIndex>,
+}
+
+impl Daemon {
+    pub fn new(index: FileIndex) -> Self {
+        Self {
+            index: RwLock::new(index),
+        }
+    }
+
+    /// Returns the total number of results and the requested page of them, sorted when asked
+    pub fn search<T, F>(
+        &self,
+        query: &str,
+        sort: Option<(SortKey, bool)>,
+        offset: usize,
+        count: Option<usize>,
+        f: F,
+    ) -> (usize, Vec<T>)
+    where
+        F: Fn(&File, PathBuf) -> T,
+    {
+        let index = self.index.read().unwrap();
+        let mut results = index.search(SearchParams::from_str(query));
+
+        if let Some((key, ascending)) = sort {
+            results.sort_by(|a, b| {
+                let ordering = key.compare(a, b);
+                if ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            });
+        }
+
+        let total = results.len();
+        let page = results
+            .into_iter()
+            .skip(offset)
+            .take(count.unwrap_or(usize::MAX))
+            .map(|(file, path)| f(file, path))
+            .collect();
+
+        (total, page)
+    }
+
+    pub fn handle(&self, request: Request) -> Response {
+        match request {
+            Request::Search {
+                query,
+                offset,
+                count,
+            } => {
+                let (total, results) = self.search(&query, None, offset, count, ResultRecord::new);
+                Response::Results { total, results }
+            }
+            Request::Ping => Response::Pong,
+        }
+    }
+
+    fn handle_connection(&self, stream: Stream) -> CaverResult<()> {
+        let is_owner = is_same_user(&stream)?;
+        let (reader, writer) = stream.split();
+        let mut reader = BufReader::new(reader);
+        let mut writer = BufWriter::new(writer);
+
+        if !is_owner {
+            let message = "permission denied, the daemon runs as another user".to_string();
+            return write_message(&mut writer, &Response::Error { message });
+        }
+
+        loop {
+            let response = match read_message(&mut reader, MAX_REQUEST_LEN) {
+                Ok(Some(request)) => self.handle(request),
+                Ok(None) => return Ok(()),
+                Err(CaverError::InvalidMessage(e)) => Response::Error {
+                    message: format!("invalid request : {e}"),
+                },
+                // The rest of the request is still to be read, there is no next one to find
+                Err(e @ CaverError::MessageTooLarge(_)) => {
+                    let message = e.to_string();
+                    return write_message(&mut writer, &Response::Error { message });
+                }
+                Err(e) => return Err(e),
+            };
+            write_message(&mut writer, &response)?;
+        }
+    }
+
+    /// Answers requests on `socket` until the process is stopped, one thread per connection
+    pub fn serve(self: Arc<Self>, socket: &str) -> CaverResult<()> {
+        let listener = listener_options(socket_name(socket)?)?
+            .try_overwrite(true)
+            .create_sync()?;
+
+        for stream in listener.incoming() {
+            let stream = match stream {
+                Ok(stream) => stream,
+                Err(e) => {
+                    warn!("connection failed : {e}");
+                    continue;
+                }
+            };
+
+            let daemon = self.clone();
+            thread::spawn(move || {
+                if let Err(e) = daemon.handle_connection(stream) {
+                    debug!("connection closed : {e}");
+                }
+            });
+        }
+        Ok(())
+    }
+
+    /// Rebuilds the index in the background every `interval`,
+    /// queries keep being answered from the old one while it is built
+    pub fn refresh_every<F>(self: &Arc<Self>, interval: Duration, rebuild: F)
+    where
+        F: Fn() -> CaverResult<FileIndex> + Send + 'static,
+    {
+        let daemon = self.clone();
+        thread::spawn(move || loop {
+            thread::sleep(interval);
+
+            let rebuild_start = Instant::now();
+            match rebuild() {
+                Ok(index) => {
+                    *daemon.index.write().unwrap() = index;
+                    info!(elapsed = ?rebuild_start.elapsed(), "index refreshed");
+                }
+                Err(e) => error!("index refresh failed : {e}"),
+            }
+        });
+    }
+}
+
+pub struct Client {
+    reader: BufReader<RecvHalf>,
+    writer: BufWriter<SendHalf>,
+}
+
+impl Client {
+    /// Fails right away when no daemon listens on `socket` or it runs as another user,
+    /// requests fail when the daemon takes over [`CLIENT_TIMEOUT`] to answer
+    pub fn connect(socket: &str) -> io::Result<Self> {
+        let stream = Stream::connect(socket_name(socket)?)?;
+        if !is_same_user(&stream)? {
+            return Err(io::Error::new(
+                io::ErrorKind::PermissionDenied,
+                "the daemon runs as another user",
+            ));
+        }
+        stream.set_recv_timeout(Some(CLIENT_TIMEOUT))?;
+        let (reader, writer) = stream.split();
+        Ok(Self {
+            reader: BufReader::new(reader),
+            writer: BufWriter::new(writer),
+        })
+    }
+
+    pub fn request(&mut self, request: &Request) -> CaverResult<Response> {
+        write_message(&mut self.writer, request)?;
+        match read_message(&mut self.reader, MAX_MESSAGE_LEN)? {
+            Some(Response::Error { message }) => Err(CaverError::Daemon(message)),
+            Some(response) => Ok(response),
+            None => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+
+    /// Returns the total number of results and the requested page of them
+    pub fn search(
+        &mut self,
+        query: &str,
+        offset: usize,
+        count: Option<usize>,
+    ) -> CaverResult<(usize, Vec<ResultRecord>)> {
+        let request = Request::Search {
+            query: query.to_string(),
+            offset,
+            count,
+        };
+
+        match self.request(&request)? {
+            Response::Results { total, results } => Ok((total, results)),
+            other => Err(CaverError::Daemon(format!("unexpected response {other:?}"))),
+        }
+    }
+    /// Every result, asked [`SEARCH_PAGE`] at a time
+    pub fn search_all(&mut self, query: &str) -> CaverResult<Vec<ResultRecord>> {
+        let mut records = Vec::new();
+        loop {
+            let (total, page) = self.search(query, records.len(), Some(SEARCH_PAGE))?;
+            let last = page.len() < SEARCH_PAGE;
+            records.extend(page);
+            if last || records.len() >= total {
+                return Ok(records);
+            }
+        }
+    }
+}
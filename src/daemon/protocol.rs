@@ -0,0 +1,99 @@
+//! Messages exchanged with `caver serve` over its local socket
+//! (a unix domain socket, or a named pipe on windows).
+//!
+//! Every message is framed as a 4 bytes little endian length followed by that many bytes of UTF-8 json.
+//! A client sends a [`Request`] and reads exactly one [`Response`] back,
+//! it can keep the connection open to send more requests.
+//! A request that can't be parsed is answered with an error, one over [`MAX_REQUEST_LEN`]
+//! is answered with an error and the connection closed.
+//!
+//! ```text
+//! -> {"type": "search", "query": "main.rs path<src>", "offset": 0, "count": 50}
+//! <- {"type": "results", "total": 2, "results": [{"name": "main.rs", "path": "...", "kind": "file", ...}]}
+//! -> {"type": "ping"}
+//! <- {"type": "pong"}
+//! ```
+
+use std::io::{self, Read, Write};
+
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+
+use crate::{
+    error::{CaverError, CaverResult},
+    output::ResultRecord,
+};
+
+/// Larger messages are refused instead of being read
+pub const MAX_MESSAGE_LEN: usize = 512 * 1024 * 1024;
+
+/// Larger requests are refused by the daemon, they only hold a query
+pub const MAX_REQUEST_LEN: usize = 4 * 1024 * 1024;
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Request {
+    Search {
+        query: String,
+        #[serde(default)]
+        offset: usize,
+        /// Every result when missing
+        #[serde(default)]
+        count: Option<usize>,
+    },
+    Ping,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum Response {
+    Results {
+        /// Number of results before `offset` and `count` are applied
+        total: usize,
+        results: Vec<ResultRecord>,
+    },
+    Pong,
+    Error {
+        message: String,
+    },
+}
+
+pub fn write_message<W: Write, T: Serialize>(mut writer: W, message: &T) -> CaverResult<()> {
+    let data = serde_json::to_vec(message).map_err(CaverError::InvalidMessage)?;
+    if data.len() > MAX_MESSAGE_LEN {
+        return Err(CaverError::MessageTooLarge(data.len()));
+    }
+
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(&data)?;
+    writer.flush()?;
+    Ok(())
+}
+
+/// Returns `None` when the other side closed the connection between two messages.
+/// Messages over `max_len` are refused before their data is read
+pub fn read_message<R: Read, T: DeserializeOwned>(
+    mut reader: R,
+    max_len: usize,
+) -> CaverResult<Option<T>> {
+    let mut len = [0; 4];
+    match reader.read_exact(&mut len) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e.into()),
+    }
+
+    let len = u32::from_le_bytes(len) as usize;
+    if len > max_len {
+        return Err(CaverError::MessageTooLarge(len));
+    }
+
+    // Grows with what is received rather than what the length claims
+    let mut data = Vec::new();
+    reader.take(len as u64).read_to_end(&mut data)?;
+    if data.len() < len {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    serde_json::from_slice(&data)
+        .map(Some)
+        .map_err(CaverError::InvalidMessage)
+}
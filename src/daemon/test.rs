@@ -0,0 +1,168 @@
+use std::{
+    io::{Cursor, Read, Write},
+    net::TcpStream,
+    process,
+    sync::Arc,
+    thread,
+    time::Duration,
+};
+
+use interprocess::local_socket::{prelude::*, Stream};
+
+use crate::{
+    error::CaverError,
+    file::{index::FileIndex, File, FileKind},
+};
+
+use super::{
+    http,
+    protocol::{read_message, write_message, Request, Response, MAX_REQUEST_LEN},
+    socket_name, Client, Daemon,
+};
+
+fn index() -> FileIndex {
+    FileIndex::new(vec![File {
+        name: "root".to_string(),
+        kind: FileKind::Directory,
+        children: (0..5)
+            .map(|i| File {
+                name: format!("file{i}.txt"),
+                size: i,
+                ..Default::default()
+            })
+            .collect(),
+        ..Default::default()
+    }])
+}
+
+#[test]
+fn message_framing() {
+    let request = Request::Search {
+        query: "main.rs".to_string(),
+        offset: 2,
+        count: None,
+    };
+
+    let mut data = Vec::new();
+    write_message(&mut data, &request).unwrap();
+    write_message(&mut data, &Request::Ping).unwrap();
+
+    let json = br#"{"type":"search","query":"main.rs","offset":2,"count":null}"#;
+    assert_eq!(&data[..4], &(json.len() as u32).to_le_bytes());
+    assert_eq!(&data[4..4 + json.len()], json);
+
+    let mut reader = Cursor::new(data);
+    let read = |reader: &mut Cursor<Vec<u8>>| read_message::<_, Request>(reader, MAX_REQUEST_LEN);
+    assert_eq!(read(&mut reader).unwrap(), Some(request));
+    assert_eq!(read(&mut reader).unwrap(), Some(Request::Ping));
+    assert_eq!(read(&mut reader).unwrap(), None);
+
+    let too_large = ((MAX_REQUEST_LEN + 1) as u32).to_le_bytes().to_vec();
+    assert!(matches!(
+        read(&mut Cursor::new(too_large)),
+        Err(CaverError::MessageTooLarge(_))
+    ));
+
+    // Cut short
+    let mut truncated = 10u32.to_le_bytes().to_vec();
+    truncated.extend_from_slice(b"{}");
+    assert!(matches!(
+        read(&mut Cursor::new(truncated)),
+        Err(CaverError::IOError(_))
+    ));
+}
+
+#[test]
+fn query_daemon() {
+    let socket = format!("caver-test-{}.sock", process::id());
+
+    let daemon = Arc::new(Daemon::new(index()));
+    let server_socket = socket.clone();
+    thread::spawn(move || daemon.serve(&server_socket).unwrap());
+
+    let mut client = (0..100)
+        .find_map(|_| {
+            Client::connect(&socket)
+                .map_err(|_| thread::sleep(Duration::from_millis(20)))
+                .ok()
+        })
+        .unwrap();
+
+    assert_eq!(client.request(&Request::Ping).unwrap(), Response::Pong);
+
+    // The connection survives a malformed request
+    let mut stream = Stream::connect(socket_name(&socket).unwrap()).unwrap();
+    let data = br#"{"type":"unknown"}"#;
+    stream
+        .write_all(&(data.len() as u32).to_le_bytes())
+        .unwrap();
+    stream.write_all(data).unwrap();
+    assert!(matches!(
+        read_message(&mut stream, usize::MAX),
+        Ok(Some(Response::Error { .. }))
+    ));
+    write_message(&mut stream, &Request::Ping).unwrap();
+    assert_eq!(
+        read_message(&mut stream, usize::MAX).unwrap(),
+        Some(Response::Pong)
+    );
+
+    let (total, results) = client.search("file", 1, Some(2)).unwrap();
+    assert_eq!(total, 5);
+    assert_eq!(results.len(), 2);
+    assert_eq!(results[0].name, "file1.txt");
+    assert_eq!(results[0].size, 1);
+    assert_eq!(results[1].path.to_string_lossy(), "root/file2.txt");
+    assert_eq!(client.search_all("file").unwrap().len(), 5);
+}
+
+fn http_get(addr: &str, url: &str) -> (u16, serde_json::Value) {
+    let mut stream = TcpStream::connect(addr).unwrap();
+    write!(stream, "GET {url} HTTP/1.0\r\nHost: {addr}\r\n\r\n").unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+
+    let (head, body) = response.split_once("\r\n\r\n").unwrap();
+    let status = head.split(' ').nth(1).unwrap().parse().unwrap();
+    (status, serde_json::from_str(body).unwrap())
+}
+
+#[test]
+fn query_http() {
+    assert!(http::bind("0.0.0.0:0", false).is_err());
+    let server = http::bind("127.0.0.1:0", false).unwrap();
+    let addr = server.server_addr().to_ip().unwrap().to_string();
+
+    let daemon = Arc::new(Daemon::new(index()));
+    thread::spawn(move || daemon.serve_http(server));
+
+    let (status, body) = http_get(
+        &addr,
+        "/search?q=file%20%3Ffile0&sort=size&ascending=0&offset=1&count=2",
+    );
+    assert_eq!(status, 200);
+    assert_eq!(body["totalResults"], 4);
+    assert_eq!(body["results"][0]["name"], "file3.txt");
+    assert_eq!(body["results"][0]["type"], "file");
+    assert_eq!(body["results"][0]["path"], "root");
+    assert_eq!(body["results"][0]["size"], "3");
+    assert_eq!(body["results"][1]["name"], "file2.txt");
+
+    // Everything's own parameter names
+    let (_, body) = http_get(&addr, "/?s=file4&json=1");
+    assert_eq!(body["totalResults"], 1);
+
+    let (status, body) = http_get(&addr, "/search?q=file&offset=&count=&sort=");
+    assert_eq!(status, 200);
+    assert_eq!(body["totalResults"], 5);
+    assert_eq!(body["results"][0]["name"], "file0.txt");
+
+    let (status, _) = http_get(&addr, "/search?q=file&sort=color");
+    assert_eq!(status, 400);
+
+    let (status, body) = http_get(&addr, "/stats");
+    assert_eq!(status, 200);
+    assert_eq!(body["disks"][0]["files"], 5);
+    assert_eq!(body["disks"][0]["size"], 10);
+}
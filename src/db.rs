@@ -0,0 +1,39 @@
+//! Indexes saved to disk with bincode, each starting with the version of its layout
+
+use std::{fs, path::Path, process};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+use crate::error::{CaverError, CaverResult, IntoCaverResult, WithContext};
+
+/// The index saved at `path`, [`CaverError::OutdatedIndex`] if it was saved with another `version`
+pub(crate) fn load<T: DeserializeOwned>(path: &Path, version: u32) -> CaverResult<T> {
+    let data = fs::read(path)
+        .into_caver_result()
+        .context(path.to_path_buf())?;
+    from_bytes(&data, version).context(path.to_path_buf())
+}
+
+fn from_bytes<T: DeserializeOwned>(data: &[u8], version: u32) -> CaverResult<T> {
+    // The version is the first serialized field, it can be checked before the rest of the layout
+    let saved: u32 = bincode::deserialize(data).into_caver_result()?;
+    if saved != version {
+        return Err(CaverError::OutdatedIndex(saved));
+    }
+
+    bincode::deserialize(data).into_caver_result()
+}
+
+/// Writes `value` to a temp file next to `path` then renames it over `path`,
+/// a process stopped while saving leaves the previous file whole
+pub(crate) fn save<T: Serialize>(path: &Path, value: &T) -> CaverResult<()> {
+    let data = bincode::serialize(value).into_caver_result()?;
+
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(format!(".{}.tmp", process::id()));
+    let res = fs::write(&temp, data).and_then(|()| fs::rename(&temp, path));
+    if res.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    res.into_caver_result().context(path.to_path_buf())
+}
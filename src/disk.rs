@@ -1,16 +1,22 @@
-use std::path::{Path, PathBuf};
+use std::{
+    fmt::{self, Display},
+    path::{Path, PathBuf},
+};
 
+#[cfg(windows)]
 use ntfs_reader::volume::Volume;
 use sysinfo::Disks;
 
-use crate::error::{CaverError, CaverResult, IntoCaverResult};
+#[cfg(windows)]
+use crate::error::IntoCaverResult;
+use crate::error::{CaverError, CaverResult};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub struct DiskLetter(char);
 
-impl ToString for DiskLetter {
-    fn to_string(&self) -> String {
-        self.0.to_string()
+impl Display for DiskLetter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
     }
 }
 
@@ -25,6 +31,7 @@ impl DiskLetter {
         Self(c)
     }
 
+    #[cfg(windows)]
     pub fn volume(&self) -> CaverResult<Volume> {
         let path = format!("\\\\.\\{}:", self.0);
         Volume::new(path).into_caver_result()
@@ -58,3 +65,35 @@ impl DiskLetter {
             .collect()
     }
 }
+
+/// A mounted disk as reported by the os
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mount {
+    pub path: PathBuf,
+    /// e.g. `NTFS`, `FAT32`, `ext4`, empty when unknown
+    pub file_system: String,
+}
+
+impl Mount {
+    pub fn get_all() -> Vec<Self> {
+        let mut mounts = Disks::new_with_refreshed_list()
+            .iter()
+            .map(|disk| Mount {
+                path: disk.mount_point().to_owned(),
+                file_system: disk.file_system().to_string_lossy().into_owned(),
+            })
+            .collect::<Vec<_>>();
+        mounts.sort_by(|a, b| a.path.cmp(&b.path));
+        mounts.dedup_by(|a, b| a.path == b.path);
+        mounts
+    }
+
+    /// Only NTFS disks have an MFT to read
+    pub fn is_ntfs(&self) -> bool {
+        self.file_system.eq_ignore_ascii_case("ntfs")
+    }
+
+    pub fn letter(&self) -> Option<DiskLetter> {
+        self.path.to_str()?.chars().next().map(DiskLetter)
+    }
+}
@@ -1,5 +1,13 @@
-use std::{io, path::PathBuf};
+use std::{
+    error::Error,
+    fmt::{self, Display},
+    io,
+    path::PathBuf,
+};
 
+use crate::keymap::Action;
+
+#[cfg(windows)]
 use ntfs_reader::errors::{NtfsReaderError, NtfsReaderResult};
 
 pub type CaverResult<T> = core::result::Result<T, CaverError>;
@@ -8,6 +16,7 @@ pub trait IntoCaverResult<T> {
     fn into_caver_result(self) -> CaverResult<T>;
 }
 
+#[cfg(windows)]
 impl<T> IntoCaverResult<T> for NtfsReaderResult<T> {
     fn into_caver_result(self) -> CaverResult<T> {
         self.map_err(|e| e.into())
@@ -26,22 +35,148 @@ impl<T> IntoCaverResult<T> for bincode::Result<T> {
     }
 }
 
+/// What was being worked on when an error happened
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Context {
+    Path(PathBuf),
+}
+
+impl From<PathBuf> for Context {
+    fn from(value: PathBuf) -> Self {
+        Self::Path(value)
+    }
+}
+
+impl Display for Context {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Path(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+pub trait WithContext<T> {
+    /// Attaches `context` to the error, if any
+    fn context(self, context: impl Into<Context>) -> CaverResult<T>;
+}
+
+impl<T> WithContext<T> for CaverResult<T> {
+    fn context(self, context: impl Into<Context>) -> CaverResult<T> {
+        self.map_err(|e| CaverError::WithContext(context.into(), Box::new(e)))
+    }
+}
+
 #[derive(Debug)]
 pub enum CaverError {
     UnableToConvertPathToString(PathBuf),
     IOError(io::Error),
     DeserializeError(bincode::ErrorKind),
     ElevationError,
+    /// The MFT of a disk could not be parsed
+    InvalidMft(Box<dyn Error + Send + Sync>),
+    /// A record listed as the child of a directory is not in the MFT
+    MissingRecord(u64),
+    /// A call to the windows api failed
+    Windows(Box<dyn Error + Send + Sync>),
+    /// The saved index was written with another layout version
+    OutdatedIndex(u32),
+    NotIndexed(PathBuf),
+    InvalidKey(String),
+    InvalidKeymap(toml::de::Error),
+    /// A chord bound to two actions, or the start of another chord
+    KeymapConflict(String, Action, Action),
+    InvalidRules(toml::de::Error),
+    InvalidContentRules(toml::de::Error),
+    InvalidKinds(toml::de::Error),
+    InvalidPattern(globset::Error),
+    InvalidMessage(serde_json::Error),
+    MessageTooLarge(usize),
+    /// An error reported by `caver serve`
+    Daemon(String),
+    Http(String),
+    WithContext(Context, Box<CaverError>),
     Unknown,
 }
 
+impl CaverError {
+    /// The error without its context
+    pub fn root(&self) -> &CaverError {
+        match self {
+            Self::WithContext(_, e) => e.root(),
+            e => e,
+        }
+    }
+}
+
+impl Display for CaverError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnableToConvertPathToString(path) => {
+                write!(f, "{} is not valid unicode", path.display())
+            }
+            Self::IOError(e) => write!(f, "{e}"),
+            Self::DeserializeError(_) => write!(f, "the index file is corrupted"),
+            Self::ElevationError => write!(
+                f,
+                "reading the MFT requires administrator rights, run caver from an elevated terminal"
+            ),
+            Self::InvalidMft(_) => write!(f, "the MFT could not be parsed"),
+            Self::MissingRecord(record) => {
+                write!(f, "MFT record {record} is referenced but does not exist")
+            }
+            Self::Windows(e) => write!(f, "{e}"),
+            Self::OutdatedIndex(version) => write!(
+                f,
+                "the index was built by another version of caver (layout {version}), run `caver reset`"
+            ),
+            Self::NotIndexed(path) => write!(f, "{} is not in the index", path.display()),
+            Self::InvalidKey(key) => write!(f, "invalid key `{key}`"),
+            Self::InvalidKeymap(_) => write!(f, "invalid keymap file"),
+            Self::KeymapConflict(chord, first, second) => {
+                write!(f, "`{chord}` is bound to both {first} and {second}")
+            }
+            Self::InvalidRules(_) => write!(f, "invalid rules file"),
+            Self::InvalidContentRules(_) => write!(f, "invalid content index rules file"),
+            Self::InvalidKinds(_) => write!(f, "invalid file kinds file"),
+            Self::InvalidPattern(e) => write!(f, "{e}"),
+            Self::InvalidMessage(_) => write!(f, "invalid message from the daemon"),
+            Self::MessageTooLarge(len) => write!(f, "message of {len} bytes is too large"),
+            Self::Daemon(message) => write!(f, "daemon error : {message}"),
+            Self::Http(message) => write!(f, "http server error : {message}"),
+            Self::WithContext(context, e) => write!(f, "{context} : {e}"),
+            Self::Unknown => write!(f, "unknown error"),
+        }
+    }
+}
+
+impl Error for CaverError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Self::IOError(e) => e.source(),
+            Self::DeserializeError(e) => Some(e),
+            Self::InvalidMft(e) => Some(e.as_ref()),
+            Self::Windows(e) => e.source(),
+            Self::InvalidKeymap(e) => Some(e),
+            Self::InvalidRules(e) => Some(e),
+            Self::InvalidContentRules(e) => Some(e),
+            Self::InvalidKinds(e) => Some(e),
+            Self::InvalidMessage(e) => Some(e),
+            // The wrapped error is already part of the message
+            Self::WithContext(_, e) => e.source(),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(windows)]
 impl From<NtfsReaderError> for CaverError {
     fn from(value: NtfsReaderError) -> Self {
         match value {
             NtfsReaderError::IOError(e) => Self::IOError(e),
             NtfsReaderError::ElevationError => Self::ElevationError,
+            NtfsReaderError::BinReadError(e) => Self::InvalidMft(Box::new(e)),
+            NtfsReaderError::WindowsError(e) => Self::Windows(Box::new(e)),
             NtfsReaderError::Unknown => Self::Unknown,
-            _ => todo!("other errors"),
         }
     }
 }
@@ -0,0 +1,464 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    io::{self, Read, Seek, SeekFrom},
+    path::{Component, Path, PathBuf},
+    sync::{Arc, Mutex, OnceLock},
+    time::SystemTime,
+};
+
+use flate2::read::GzDecoder;
+use rayon::iter::{IntoParallelRefIterator, IntoParallelRefMutIterator, ParallelIterator};
+use tracing::debug;
+use zip::ZipArchive;
+
+use super::{attributes, File, FileKind};
+
+/// Ends the name of the directory listing an archive, next to it : `foo.zip!`
+pub const SEPARATOR: char = '!';
+
+/// Archives with more entries are left as they are
+const MAX_ENTRIES: usize = 100_000;
+
+/// Tar.gz archives are unpacked whole to be listed, the ones unpacking to more are left as they are
+const MAX_UNPACKED_SIZE: u64 = 1 << 30;
+
+/// Archives [`read_entry`] keeps open, the most recently read
+const KEPT_ARCHIVES: usize = 8;
+
+/// Unpacked tar.gz data [`read_entry`] keeps over all archives,
+/// a larger archive is unpacked again for each entry
+const MAX_KEPT_SIZE: u64 = 64 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Zip,
+    Tar,
+    TarGz,
+}
+
+impl Format {
+    fn of(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        let ends_with = |extensions: &[&str]| extensions.iter().any(|e| name.ends_with(e));
+        if ends_with(&[".zip", ".jar", ".nupkg"]) {
+            Some(Self::Zip)
+        } else if ends_with(&[".tar.gz", ".tgz"]) {
+            Some(Self::TarGz)
+        } else if ends_with(&[".tar"]) {
+            Some(Self::Tar)
+        } else {
+            None
+        }
+    }
+}
+
+/// The parts of a path inside an archive, `/` and `\` both separate them
+fn parts(name: &str) -> impl Iterator<Item = &str> {
+    name.split(['/', '\\'])
+        .filter(|part| !matches!(*part, "" | "." | ".."))
+}
+
+/// The name of an entry cleaned up like [`add`] does, with `/` separators
+fn clean(name: &str) -> String {
+    parts(name).collect::<Vec<_>>().join("/")
+}
+
+/// Fails once more than [`MAX_ENTRIES`] were counted
+fn count_entry(count: &mut usize) -> io::Result<()> {
+    *count += 1;
+    match *count > MAX_ENTRIES {
+        true => Err(io::Error::other(format!("more than {MAX_ENTRIES} entries"))),
+        false => Ok(()),
+    }
+}
+
+/// Fails reads past [`MAX_UNPACKED_SIZE`]
+struct Limited<R> {
+    reader: R,
+    left: u64,
+}
+
+impl<R: Read> Limited<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            reader,
+            left: MAX_UNPACKED_SIZE,
+        }
+    }
+}
+
+impl<R: Read> Read for Limited<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let read = self.reader.read(buf)?;
+        self.left = (self.left.checked_sub(read as u64)).ok_or_else(|| {
+            io::Error::other(format!("unpacks to more than {MAX_UNPACKED_SIZE} bytes"))
+        })?;
+        Ok(read)
+    }
+}
+
+fn open_tar_gz(path: &Path) -> io::Result<tar::Archive<Limited<GzDecoder<fs::File>>>> {
+    Ok(tar::Archive::new(Limited::new(GzDecoder::new(
+        fs::File::open(path)?,
+    ))))
+}
+
+/// Seconds since the unix epoch of a date without time zone, taken as UTC
+fn unix_time(year: i64, month: i64, day: i64, hour: i64, minute: i64, second: i64) -> i64 {
+    // Days from civil, with years starting in march so leap days come last
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    days * 86_400 + hour * 3600 + minute * 60 + second
+}
+
+/// Adds the entry at `name` under `root`, with the directories above it
+fn add(root: &mut File, name: &str, kind: FileKind, size: u64, modified: Option<i64>) {
+    let mut parts = parts(name).collect::<Vec<_>>();
+    let Some(last) = parts.pop() else {
+        return;
+    };
+
+    let mut dir = root;
+    for part in parts {
+        if dir.child(part).is_none() {
+            dir.insert_child(entry(part, FileKind::Directory, 0, None));
+        }
+        dir = dir.child_mut(part).unwrap();
+    }
+
+    match dir.child_mut(last) {
+        // Listed after a file under it
+        Some(existing) if existing.is_dir() => existing.modified = modified,
+        Some(_) => {}
+        None => {
+            dir.insert_child(entry(last, kind, size, modified));
+        }
+    }
+}
+
+fn entry(name: &str, kind: FileKind, size: u64, modified: Option<i64>) -> File {
+    File {
+        name: name.to_string(),
+        kind,
+        size,
+        modified,
+        attributes: attributes::IN_ARCHIVE,
+        ..Default::default()
+    }
+}
+
+fn list_zip(path: &Path, root: &mut File) -> io::Result<()> {
+    let mut zip = ZipArchive::new(fs::File::open(path)?)?;
+    if zip.len() > MAX_ENTRIES {
+        return Err(io::Error::other(format!("more than {MAX_ENTRIES} entries")));
+    }
+
+    for i in 0..zip.len() {
+        let file = zip.by_index_raw(i)?;
+        let modified = file.last_modified().map(|t| {
+            let field = |n: u8| n as i64;
+            unix_time(
+                t.year() as i64,
+                field(t.month()),
+                field(t.day()),
+                field(t.hour()),
+                field(t.minute()),
+                field(t.second()),
+            )
+        });
+        let kind = match file.is_dir() {
+            true => FileKind::Directory,
+            false => FileKind::File,
+        };
+        add(root, file.name(), kind, file.size(), modified);
+    }
+    Ok(())
+}
+
+/// `None` for links, devices and extensions of the format
+fn tar_kind(header: &tar::Header) -> Option<FileKind> {
+    match header.entry_type() {
+        tar::EntryType::Directory => Some(FileKind::Directory),
+        tar::EntryType::Regular | tar::EntryType::Continuous => Some(FileKind::File),
+        _ => None,
+    }
+}
+
+fn list_tar<R: Read>(entries: tar::Entries<'_, R>, root: &mut File) -> io::Result<()> {
+    let mut count = 0;
+    for file in entries {
+        let file = file?;
+        count_entry(&mut count)?;
+        let header = file.header();
+        let Some(kind) = tar_kind(header) else {
+            continue;
+        };
+        let modified = header.mtime().ok().map(|t| t as i64);
+        let name = file.path()?.to_string_lossy().into_owned();
+        add(root, &name, kind, header.size()?, modified);
+    }
+    Ok(())
+}
+
+/// The entries of the archive `file` at `path`, in a directory named after it with a trailing `!`.
+/// `None` if it isn't an archive
+fn list(file: &File, path: &Path) -> Option<io::Result<File>> {
+    let format = Format::of(&file.name)?;
+    let mut root = entry(
+        &format!("{}{SEPARATOR}", file.name),
+        FileKind::Directory,
+        0,
+        file.modified,
+    );
+
+    let res = match format {
+        Format::Zip => list_zip(path, &mut root),
+        // Seeks over the data of the entries
+        Format::Tar => fs::File::open(path)
+            .map(tar::Archive::new)
+            .and_then(|mut tar| list_tar(tar.entries_with_seek()?, &mut root)),
+        Format::TarGz => open_tar_gz(path).and_then(|mut tar| list_tar(tar.entries()?, &mut root)),
+    };
+    Some(res.map(|()| root))
+}
+
+/// Lists every archive under the directory `dir` at `path` next to it, see [`SEPARATOR`].
+/// Archives that can't be read, with more than [`MAX_ENTRIES`] entries
+/// or unpacking to more than [`MAX_UNPACKED_SIZE`] are left as they are
+pub(super) fn expand(dir: &mut File, path: &Path) {
+    dir.children
+        .par_iter_mut()
+        .filter(|child| child.is_dir() && !child.is_in_archive())
+        .for_each(|child| expand(child, &path.join(&child.name)));
+
+    let listed = (dir.children.par_iter())
+        .filter(|child| !child.is_dir() && !child.is_in_archive())
+        .filter_map(|child| {
+            let path = path.join(&child.name);
+            match list(child, &path)? {
+                Ok(listed) => Some(listed),
+                Err(e) => {
+                    debug!(path = %path.display(), "archive not listed : {e}");
+                    None
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for listed in listed {
+        // A file already has its name
+        if dir.child(&listed.name).is_none() {
+            dir.insert_child(listed);
+        }
+    }
+}
+
+/// The archive holding `path`, its format and the name of the entry in it. `None` if no
+/// directory of `path` is the listing of an archive : named after an archive file next to it
+/// with a trailing `!`, and not on the disk itself
+fn split(path: &Path) -> Option<(PathBuf, Format, String)> {
+    let mut archive = PathBuf::new();
+    let mut components = path.components();
+    let mut format = None;
+    for component in components.by_ref() {
+        if let Component::Normal(name) = component {
+            let listing = (name.to_str())
+                .and_then(|name| name.strip_suffix(SEPARATOR))
+                .and_then(|name| Some((name, Format::of(name)?)));
+            if let Some((name, of)) = listing {
+                if archive.join(name).is_file() && !archive.join(component).exists() {
+                    archive.push(name);
+                    format = Some(of);
+                    break;
+                }
+            }
+        }
+        archive.push(component);
+    }
+
+    let entry = components
+        .map(|part| part.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/");
+    Some((archive, format?, entry)).filter(|(_, _, entry)| !entry.is_empty())
+}
+
+/// Reads `reader` up to `max_size`, `None` if it is larger
+fn read_at_most(reader: impl Read, max_size: Option<u64>) -> io::Result<Option<Vec<u8>>> {
+    let mut bytes = Vec::new();
+    let limit = max_size.map_or(u64::MAX, |max| max.saturating_add(1));
+    reader.take(limit).read_to_end(&mut bytes)?;
+    Ok(max_size
+        .is_none_or(|max| bytes.len() as u64 <= max)
+        .then_some(bytes))
+}
+
+/// Where [`read_entry`] finds the entries of an archive, by their name as [`clean`] gives it
+enum Entries {
+    /// The index of each entry
+    Zip(Mutex<ZipArchive<fs::File>>, HashMap<String, usize>),
+    /// Where the data of each entry starts in the file, and its size
+    Tar(HashMap<String, (u64, u64)>),
+    /// The data of each entry, and its total size
+    TarGz(HashMap<String, Vec<u8>>, u64),
+    /// A tar.gz archive unpacking to more than [`MAX_KEPT_SIZE`]
+    TooLarge,
+}
+
+impl Entries {
+    fn open(path: &Path, format: Format) -> io::Result<Self> {
+        let mut count = 0;
+        match format {
+            Format::Zip => {
+                let zip = ZipArchive::new(fs::File::open(path)?)?;
+                let mut names = HashMap::new();
+                for i in 0..zip.len() {
+                    count_entry(&mut count)?;
+                    if let Some(name) = zip.name_for_index(i) {
+                        names.insert(clean(name), i);
+                    }
+                }
+                Ok(Self::Zip(Mutex::new(zip), names))
+            }
+            Format::Tar => {
+                let mut tar = tar::Archive::new(fs::File::open(path)?);
+                let mut entries = HashMap::new();
+                for file in tar.entries_with_seek()? {
+                    let file = file?;
+                    count_entry(&mut count)?;
+                    if tar_kind(file.header()) == Some(FileKind::File) {
+                        let name = clean(&file.path()?.to_string_lossy());
+                        entries.insert(name, (file.raw_file_position(), file.size()));
+                    }
+                }
+                Ok(Self::Tar(entries))
+            }
+            Format::TarGz => {
+                let mut tar = open_tar_gz(path)?;
+                let (mut entries, mut size) = (HashMap::new(), 0);
+                for file in tar.entries()? {
+                    let mut file = file?;
+                    count_entry(&mut count)?;
+                    if tar_kind(file.header()) != Some(FileKind::File) {
+                        continue;
+                    }
+
+                    size += file.size();
+                    if size > MAX_KEPT_SIZE {
+                        return Ok(Self::TooLarge);
+                    }
+                    let name = clean(&file.path()?.to_string_lossy());
+                    let mut bytes = Vec::new();
+                    file.read_to_end(&mut bytes)?;
+                    entries.insert(name, bytes);
+                }
+                Ok(Self::TarGz(entries, size))
+            }
+        }
+    }
+
+    /// Unpacked data kept in memory
+    fn kept_size(&self) -> u64 {
+        match self {
+            Self::TarGz(_, size) => *size,
+            _ => 0,
+        }
+    }
+
+    fn read(&self, path: &Path, entry: &str, max_size: Option<u64>) -> io::Result<Option<Vec<u8>>> {
+        match self {
+            Self::Zip(zip, names) => match names.get(entry) {
+                Some(&i) => read_at_most(zip.lock().unwrap().by_index(i)?, max_size),
+                None => Ok(None),
+            },
+            Self::Tar(entries) => match entries.get(entry) {
+                Some(&(start, size)) => {
+                    let mut file = fs::File::open(path)?;
+                    file.seek(SeekFrom::Start(start))?;
+                    read_at_most(file.take(size), max_size)
+                }
+                None => Ok(None),
+            },
+            Self::TarGz(entries, _) => Ok(entries
+                .get(entry)
+                .filter(|bytes| max_size.is_none_or(|max| bytes.len() as u64 <= max))
+                .cloned()),
+            Self::TooLarge => {
+                for file in open_tar_gz(path)?.entries()? {
+                    let file = file?;
+                    if clean(&file.path()?.to_string_lossy()) == entry {
+                        return read_at_most(file, max_size);
+                    }
+                }
+                Ok(None)
+            }
+        }
+    }
+}
+
+/// An archive kept open by [`read_entry`], as its file was when opened
+struct Kept {
+    path: PathBuf,
+    len: u64,
+    modified: Option<SystemTime>,
+    /// Opened by the first read, the others wait for it. `None` if it can't be read
+    entries: OnceLock<Option<Entries>>,
+}
+
+/// The most recently read first
+static KEPT: Mutex<VecDeque<Arc<Kept>>> = Mutex::new(VecDeque::new());
+
+/// The archive at `path` kept open, opened again if its file changed
+fn kept(path: &Path) -> Option<Arc<Kept>> {
+    let metadata = fs::metadata(path).ok()?;
+    let (len, modified) = (metadata.len(), metadata.modified().ok());
+
+    let mut archives = KEPT.lock().unwrap();
+    let i = (archives.iter())
+        .position(|kept| kept.path == path && kept.len == len && kept.modified == modified);
+    let kept = match i {
+        Some(i) => archives.remove(i).unwrap(),
+        None => Arc::new(Kept {
+            path: path.to_path_buf(),
+            len,
+            modified,
+            entries: OnceLock::new(),
+        }),
+    };
+    archives.retain(|outdated| outdated.path != path);
+    archives.push_front(kept.clone());
+
+    archives.truncate(KEPT_ARCHIVES);
+    let mut size = 0;
+    let keep = (archives.iter())
+        .take_while(|kept| {
+            size += (kept.entries.get().and_then(Option::as_ref)).map_or(0, Entries::kept_size);
+            size <= MAX_KEPT_SIZE
+        })
+        .count();
+    // The archive being read stays
+    archives.truncate(keep.max(1));
+    Some(kept)
+}
+
+/// The content of the entry at `path`, under the listing of an archive.
+/// `None` if there is none or it is larger than `max_size`.
+/// The most recently read archives are kept open, see [`KEPT_ARCHIVES`]
+pub(crate) fn read_entry(path: &Path, max_size: Option<u64>) -> Option<Vec<u8>> {
+    let (archive, format, entry) = split(path)?;
+    let kept = kept(&archive)?;
+    let entries = kept.entries.get_or_init(|| {
+        Entries::open(&archive, format)
+            .inspect_err(|e| debug!(path = %archive.display(), "archive not read : {e}"))
+            .ok()
+    });
+
+    (entries.as_ref()?.read(&archive, &entry, max_size))
+        .inspect_err(|e| debug!(path = %path.display(), "entry not read : {e}"))
+        .ok()?
+}
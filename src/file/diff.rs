@@ -0,0 +1,187 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::atomic::AtomicBool,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{output::path_repr, search::SearchParams};
+
+use super::{
+    index::{search_disk, FileIndex},
+    File,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ChangeKind {
+    Added,
+    Removed,
+    /// Renamed or moved, found by its [`File::id`] and creation date
+    Moved,
+    Resized,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Added => "added",
+            Self::Removed => "removed",
+            Self::Moved => "moved",
+            Self::Resized => "resized",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Change {
+    pub kind: ChangeKind,
+    /// In the new index, except for removed files
+    #[serde(with = "path_repr")]
+    pub path: PathBuf,
+    /// Where a moved file was
+    #[serde(with = "path_repr::optional", default)]
+    pub from: Option<PathBuf>,
+    pub old_size: Option<u64>,
+    pub new_size: Option<u64>,
+}
+
+/// The files of the disk named `name` matching `params`, by path
+fn files<'a>(
+    index: &'a FileIndex,
+    name: &str,
+    params: &SearchParams,
+) -> HashMap<PathBuf, &'a File> {
+    let Some(disk) = index.disks().iter().find(|disk| disk.name == name) else {
+        return HashMap::new();
+    };
+
+    let cancel = AtomicBool::new(false);
+    params
+        .filter_content(
+            search_disk(disk, params, &cancel),
+            index.content.as_ref(),
+            &cancel,
+        )
+        .into_iter()
+        .map(|(file, path)| (path, file))
+        .collect()
+}
+
+fn diff_disk(old: HashMap<PathBuf, &File>, new: HashMap<PathBuf, &File>) -> Vec<Change> {
+    let mut changes = Vec::new();
+
+    // Files that left their path, by id, to find where they went.
+    // Hard links share their id, so there can be several
+    let mut gone = HashMap::<_, Vec<(&PathBuf, &File)>>::new();
+    for (path, file) in &old {
+        match new.get(path) {
+            Some(new_file) if !file.is_dir() && new_file.size != file.size => {
+                changes.push(Change {
+                    kind: ChangeKind::Resized,
+                    path: path.clone(),
+                    from: None,
+                    old_size: Some(file.size),
+                    new_size: Some(new_file.size),
+                });
+            }
+            Some(_) => {}
+            None if file.id != 0 => {
+                (gone.entry((file.id, file.kind)).or_default()).push((path, *file));
+            }
+            None => changes.push(Change {
+                kind: ChangeKind::Removed,
+                path: path.clone(),
+                from: None,
+                old_size: Some(file.size),
+                new_size: None,
+            }),
+        }
+    }
+
+    let mut moves = Vec::new();
+    for (path, file) in &new {
+        if old.contains_key(path) {
+            continue;
+        }
+
+        // A reused MFT record or inode has another creation date
+        let moved = gone.get_mut(&(file.id, file.kind)).and_then(|files| {
+            let i = (files.iter()).position(|(_, old_file)| old_file.created == file.created)?;
+            Some(files.swap_remove(i))
+        });
+        match moved {
+            Some((from, old_file)) => moves.push(Change {
+                kind: ChangeKind::Moved,
+                path: path.clone(),
+                from: Some(from.clone()),
+                old_size: Some(old_file.size),
+                new_size: Some(file.size),
+            }),
+            None => changes.push(Change {
+                kind: ChangeKind::Added,
+                path: path.clone(),
+                from: None,
+                old_size: None,
+                new_size: Some(file.size),
+            }),
+        }
+    }
+
+    changes.extend(gone.into_values().flatten().map(|(path, file)| Change {
+        kind: ChangeKind::Removed,
+        path: path.clone(),
+        from: None,
+        old_size: Some(file.size),
+        new_size: None,
+    }));
+
+    // The unchanged content of a moved directory is only reported through it
+    let moved = moves
+        .iter()
+        .filter_map(|change| Some((change.from.as_deref()?, change.path.as_path())))
+        .collect::<HashSet<_>>();
+    let followed_parent = |change: &Change| {
+        let from = change.from.as_deref()?;
+        let parents = (from.parent()?, change.path.parent()?);
+        Some(
+            from.file_name() == change.path.file_name()
+                && change.old_size == change.new_size
+                && moved.contains(&parents),
+        )
+    };
+    let followed = moves
+        .iter()
+        .map(|change| followed_parent(change) == Some(true))
+        .collect::<Vec<_>>();
+    changes.extend(
+        moves
+            .into_iter()
+            .zip(followed)
+            .filter_map(|(change, followed)| (!followed).then_some(change)),
+    );
+
+    changes
+}
+
+impl FileIndex {
+    /// What changed from `self` to `new` among the files matching `params`, sorted by path
+    pub fn diff(&self, new: &FileIndex, params: &SearchParams) -> Vec<Change> {
+        let mut names = self
+            .disks()
+            .iter()
+            .map(|disk| &disk.name)
+            .collect::<Vec<_>>();
+        names.extend(new.disks().iter().map(|disk| &disk.name));
+        names.sort();
+        names.dedup();
+
+        let mut changes = names
+            .into_iter()
+            .flat_map(|name| diff_disk(files(self, name, params), files(new, name, params)))
+            .collect::<Vec<_>>();
+        changes.sort_by(|a, b| a.path.cmp(&b.path));
+        changes
+    }
+}
@@ -0,0 +1,127 @@
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    hash::Hash,
+    io::{self, Read},
+    path::{Path, PathBuf},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tracing::debug;
+use xxhash_rust::xxh3::Xxh3;
+
+use crate::search::{io_pool, SearchParams};
+
+use super::{attributes, index::FileIndex, File};
+
+/// Files of a size at most this are fully hashed in the first pass
+const PARTIAL: u64 = 4096;
+
+/// Files with the same content
+#[derive(Debug)]
+pub struct DupeGroup<'a> {
+    /// Size of each file
+    pub size: u64,
+    pub files: Vec<(&'a File, PathBuf)>,
+}
+
+impl DupeGroup<'_> {
+    /// Space freed by keeping a single copy
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.files.len() as u64 - 1)
+    }
+}
+
+fn hash(path: &Path, limit: u64) -> io::Result<u128> {
+    let mut reader = fs::File::open(path)?.take(limit);
+    let mut hasher = Xxh3::new();
+    let mut buf = vec![0; 1 << 16];
+    loop {
+        match reader.read(&mut buf)? {
+            0 => return Ok(hasher.digest128()),
+            n => hasher.update(&buf[..n]),
+        }
+    }
+}
+
+type Candidate<'a> = (&'a File, PathBuf);
+
+/// Splits the groups by `key`, dropping the files it fails on and the groups left with one file
+fn split<'a, K: Eq + Hash + Send>(
+    groups: Vec<Vec<Candidate<'a>>>,
+    key: impl Fn(&Candidate) -> io::Result<K> + Sync,
+) -> Vec<Vec<Candidate<'a>>> {
+    groups
+        .into_par_iter()
+        .flat_map(|group| {
+            let keyed = group
+                .into_par_iter()
+                .filter_map(|candidate| match key(&candidate) {
+                    Ok(key) => Some((key, candidate)),
+                    Err(e) => {
+                        debug!(path = %candidate.1.display(), "not compared : {e}");
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+
+            let mut by_key = HashMap::<K, Vec<_>>::new();
+            for (key, candidate) in keyed {
+                by_key.entry(key).or_default().push(candidate);
+            }
+            by_key
+                .into_values()
+                .filter(|group| group.len() > 1)
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+impl FileIndex {
+    /// Groups the files matching `params` by content, the most wasted space first.
+    /// Only files of the same size are read, first their start then the whole file,
+    /// on the I/O threads. The entries of archives take no space of their own, files not
+    /// on the disk would be downloaded to be read, they are left out. Hard links are one file,
+    /// only the first found is kept
+    pub fn dupes(&self, params: SearchParams) -> Vec<DupeGroup<'_>> {
+        let mut by_size = HashMap::<u64, Vec<_>>::new();
+        let mut linked = HashSet::new();
+        for (file, path) in self.search(params) {
+            if file.is_dir()
+                || file.is_in_archive()
+                || file.attributes & attributes::NOT_ON_DISK != 0
+                || file.size == 0
+            {
+                continue;
+            }
+            // Ids are only unique on their disk
+            if file.id != 0 && !linked.insert((self.disk_of(&path), file.id)) {
+                continue;
+            }
+            by_size.entry(file.size).or_default().push((file, path));
+        }
+        let groups = by_size.into_values().filter(|group| group.len() > 1);
+
+        let groups = io_pool().install(|| {
+            let groups = split(groups.collect(), |(_, path)| hash(path, PARTIAL));
+            split(groups, |(file, path)| match file.size <= PARTIAL {
+                // Already hashed whole
+                true => Ok(0),
+                false => hash(path, u64::MAX),
+            })
+        });
+
+        let mut groups = groups
+            .into_iter()
+            .map(|mut files| {
+                files.sort_by(|a, b| a.1.cmp(&b.1));
+                DupeGroup {
+                    size: files[0].0.size,
+                    files,
+                }
+            })
+            .collect::<Vec<_>>();
+        groups.sort_by_key(|group| std::cmp::Reverse(group.wasted()));
+        groups
+    }
+}
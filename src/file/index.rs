@@ -1,184 +1,589 @@
 use std::{
-    cell::LazyCell,
-    collections::HashMap,
-    fs,
-    path::{Path, PathBuf},
-    sync::{Arc, Mutex},
-    time::Instant,
+    cmp::{self, Reverse},
+    fmt::{self, Display},
+    path::{Component, Path, PathBuf},
+    slice,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        OnceLock,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 
-use ntfs_reader::{
-    api::{FIRST_NORMAL_RECORD, ROOT_RECORD},
-    mft::Mft,
-};
 use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
 use serde::{Deserialize, Serialize};
+use tracing::{debug, info, info_span};
 
 use crate::{
-    disk::DiskLetter,
-    error::{CaverResult, IntoCaverResult},
-    search::SearchParams,
+    db,
+    disk::Mount,
+    error::{CaverResult, IntoCaverResult, WithContext},
+    output::path_repr,
+    progress::ProgressFn,
+    rules::{Filter, RelevantRules, Rules},
+    search::{ContentIndex, SearchParams},
+};
+
+#[cfg(windows)]
+use super::mft;
+use super::{
+    archive, compare_names,
+    kinds::{ExtensionCount, Extensions, Kinds},
+    names::NameIndex,
+    walk::{self, unix_timestamp},
+    File, Node,
 };
 
-use super::File;
+/// How a disk or walked root was handled by the last indexing
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceStatus {
+    /// Read from the MFT
+    Indexed,
+    /// Indexed by walking its directories, it has no MFT
+    Walked,
+    /// Indexed by walking its directories as its MFT couldn't be read, e.g. without elevation
+    WalkedInstead(String),
+    /// Nothing could be indexed there, e.g. a card reader without a card
+    Skipped(String),
+    Failed(String),
+}
 
-#[derive(Debug)]
-pub struct GuardedFile {
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Source {
+    /// Same as the name of the root [`File`] when indexed
     pub name: String,
-    pub children: Vec<Arc<Mutex<GuardedFile>>>,
+    pub file_system: String,
+    pub status: SourceStatus,
+    /// The part of the rules that applied to it, it is indexed again when they change
+    pub rules: RelevantRules,
+    /// When it was last indexed, in seconds since the unix epoch
+    pub indexed: Option<i64>,
+    /// How long indexing it took
+    pub index_time: Duration,
 }
 
-impl GuardedFile {
-    pub fn unguard(self) -> File {
-        File {
-            name: self.name,
-            children: self
-                .children
-                .into_iter()
-                .map(|file| {
-                    Arc::try_unwrap(file)
-                        .unwrap()
-                        .into_inner()
-                        .unwrap()
-                        .unguard()
-                })
-                .collect(),
+impl Display for SourceStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Indexed => write!(f, "indexed"),
+            Self::Walked => write!(f, "walked"),
+            Self::WalkedInstead(reason) => write!(f, "walked, {reason}"),
+            Self::Skipped(reason) => write!(f, "skipped, {reason}"),
+            Self::Failed(reason) => write!(f, "failed, {reason}"),
+        }
+    }
+}
+
+impl Display for Source {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.file_system.as_str() {
+            "" => write!(f, "{} : {}", self.name, self.status),
+            fs => write!(f, "{} ({fs}) : {}", self.name, self.status),
         }
     }
 }
 
+/// How many extensions and names [`FileIndex::stats`] lists
+pub const STATS_TOP: usize = 5;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SourceStats {
+    pub name: String,
+    pub status: SourceStatus,
+    pub files: u64,
+    pub directories: u64,
+    pub size: u64,
+    /// See [`Source::indexed`]
+    pub indexed: Option<i64>,
+    pub index_ms: u64,
+    /// Bytes it takes in the saved index
+    pub index_size: u64,
+    /// The most common extensions
+    pub top_extensions: Vec<ExtensionCount>,
+    /// The extensions taking the most space
+    pub largest_extensions: Vec<ExtensionCount>,
+    #[serde(with = "path_repr::optional")]
+    pub deepest: Option<PathBuf>,
+    /// The longest first, in characters
+    pub longest_names: Vec<String>,
+}
+
+/// The deepest path under `disk`, the first one found, and its longest names.
+/// The entries of archives are left out
+fn extremes(disk: &File) -> (Option<PathBuf>, Vec<String>) {
+    let mut deepest = (0, None);
+    let mut longest = Vec::<(usize, &str)>::with_capacity(STATS_TOP + 1);
+
+    let mut iter = disk.iter();
+    while let Some(entry) = iter.next_entry() {
+        if entry.file.is_in_archive() {
+            iter.skip_children();
+            continue;
+        }
+
+        if entry.depth > deepest.0 {
+            deepest = (entry.depth, Some(entry.path.to_path_buf()));
+        }
+
+        let len = entry.file.name.chars().count();
+        if longest.len() < STATS_TOP || longest.last().is_some_and(|(last, _)| len > *last) {
+            let i = longest.partition_point(|(other, _)| *other >= len);
+            longest.insert(i, (len, &entry.file.name));
+            longest.truncate(STATS_TOP);
+        }
+    }
+
+    let longest = longest.into_iter().map(|(_, name)| name.to_string());
+    (deepest.1, longest.collect())
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct FileIndex {
-    pub disks: Vec<File>,
+    version: u32,
+    /// Positions in it are kept by `names`, see [`FileIndex::disks_mut`]
+    disks: Vec<File>,
+    pub sources: Vec<Source>,
+    /// Finds files by name without visiting the others,
+    /// dropped by [`FileIndex::get_mut`] and [`FileIndex::disks_mut`]
+    names: Option<Box<NameIndex>>,
+    /// Counted when indexing, not updated by [`FileIndex::get_mut`]
+    pub extensions: Extensions,
+    /// Answers `content<...>` for the files it holds, saved apart at [`ContentIndex::SAVE_PATH`]
+    #[serde(skip)]
+    pub content: Option<ContentIndex>,
+    /// What `kind<...>` matches in searches, see [`Kinds::load`]
+    #[serde(skip)]
+    pub kinds: Kinds,
+    /// See [`FileIndex::stats`]
+    #[serde(skip)]
+    stats: OnceLock<Vec<SourceStats>>,
 }
 
 impl FileIndex {
-    pub const SAVE_PATH: LazyCell<PathBuf> = LazyCell::new(|| Path::new("target/db").to_owned());
+    pub const SAVE_PATH: &'static str = "target/db";
+
+    /// Bumped every time the serialized layout of the index changes
+    pub const VERSION: u32 = 12;
+
+    /// The children of `disks` must be sorted for [`FileIndex::get`], see [`File::insert_child`]
+    pub fn new(disks: Vec<File>) -> Self {
+        let sources = disks
+            .iter()
+            .map(|disk| Source {
+                name: disk.name.clone(),
+                file_system: String::new(),
+                status: SourceStatus::Indexed,
+                rules: Rules::default().relevant_to(&disk.name),
+                indexed: unix_timestamp(Ok(SystemTime::now())),
+                index_time: Duration::ZERO,
+            })
+            .collect();
+
+        Self {
+            version: Self::VERSION,
+            names: Some(Box::new(NameIndex::build(&disks))),
+            extensions: Extensions::build(&disks),
+            disks,
+            sources,
+            content: None,
+            kinds: Kinds::default(),
+            stats: OnceLock::new(),
+        }
+    }
+
+    fn from_sources(indexed: Vec<(Source, Option<File>)>) -> Self {
+        let (sources, disks): (Vec<_>, Vec<_>) = indexed.into_iter().unzip();
+        let disks = disks.into_iter().flatten().collect::<Vec<_>>();
+        Self {
+            version: Self::VERSION,
+            names: Some(Box::new(NameIndex::build(&disks))),
+            extensions: Extensions::build(&disks),
+            disks,
+            sources,
+            content: None,
+            kinds: Kinds::default(),
+            stats: OnceLock::new(),
+        }
+    }
 
+    /// Indexes every mounted disk with the default rules,
+    /// a disk that fails is recorded in [`FileIndex::sources`] without stopping the others.
+    /// Disks whose MFT can't be read, e.g. without elevation, are walked instead
     pub fn create() -> CaverResult<Self> {
-        Ok(Self {
-            disks: DiskLetter::get_all()?
+        Self::create_with(&Rules::default(), &|_| {})
+    }
+
+    /// Same as [`FileIndex::create`] with the given rules, calling `progress` as the disks are read
+    pub fn create_with(rules: &Rules, progress: ProgressFn) -> CaverResult<Self> {
+        let filter = Filter::new(rules)?;
+
+        Ok(Self::from_sources(
+            Mount::get_all()
                 .par_iter()
-                .map(|diskletter| {
-                    println!("retrieving mft for {} ...", diskletter.to_string());
-
-                    let mft = Mft::new(diskletter.volume()?).into_caver_result()?;
-
-                    println!("indexing {} ...", diskletter.to_string());
-
-                    let mut children_refs = HashMap::new();
-                    let mut roots = Vec::new();
-                    let old_files = (FIRST_NORMAL_RECORD..mft.max_record)
-                        .into_iter()
-                        .map(|index| {
-                            if !mft.record_exists(index) {
-                                return None;
-                            };
-
-                            let file = mft.get_record(index)?;
-                            let file_name = file.get_best_file_name(&mft)?;
-
-                            if !file.is_used() {
-                                return None;
-                            }
-
-                            let parent_index = file_name.parent();
-                            if parent_index == ROOT_RECORD {
-                                roots.push((index) as usize)
-                            } else {
-                                children_refs
-                                    .entry((parent_index) as usize)
-                                    .or_insert(Vec::new())
-                                    .push((index) as usize)
-                            }
-
-                            Some(file_name.to_string())
-                        })
-                        .collect::<Vec<_>>();
-
-                    fn build_tree(
-                        index: usize,
-                        old_files: &[Option<String>],
-                        children_cache: &HashMap<usize, Vec<usize>>,
-                    ) -> File {
-                        let mut file = File {
-                            name: old_files[index - FIRST_NORMAL_RECORD as usize]
-                                .clone()
-                                .unwrap(),
-                            children: vec![],
-                        };
-
-                        if let Some(children_indices) = children_cache.get(&index) {
-                            for &child_index in children_indices {
-                                file.children.push(build_tree(
-                                    child_index,
-                                    old_files,
-                                    children_cache,
-                                ));
-                            }
-                        }
-
-                        file
-                    }
-
-                    let mut files = Vec::new();
-                    for &root_index in &roots {
-                        files.push(build_tree(root_index, &old_files, &children_refs));
-                    }
-
-                    Ok(File {
-                        children: files,
-                        name: diskletter.path_as_str(),
-                    })
-                })
-                .collect::<CaverResult<Vec<File>>>()?,
+                .map(|mount| Self::index_mount(mount, rules, &filter, progress))
+                .collect(),
+        ))
+    }
+
+    fn index_mount(
+        mount: &Mount,
+        rules: &Rules,
+        filter: &Filter,
+        progress: ProgressFn,
+    ) -> (Source, Option<File>) {
+        let _span = info_span!("index", disk = %mount.path.display()).entered();
+        let start = Instant::now();
+
+        let name = mount.path.to_string_lossy().into_owned();
+        let mut source = Source {
+            rules: rules.relevant_to(&name),
+            name,
+            file_system: mount.file_system.clone(),
+            status: SourceStatus::Walked,
+            indexed: None,
+            index_time: Duration::ZERO,
+        };
+
+        let mut disk = Self::index_mount_inner(mount, &mut source, filter, progress);
+        if let Some(disk) = disk.as_mut().filter(|_| rules.archives) {
+            archive::expand(disk, &mount.path);
+        }
+        source.indexed = unix_timestamp(Ok(SystemTime::now()));
+        source.index_time = start.elapsed();
+        info!(elapsed = ?source.index_time, "{}", source.status);
+        (source, disk)
+    }
+
+    fn index_mount_inner(
+        mount: &Mount,
+        source: &mut Source,
+        filter: &Filter,
+        progress: ProgressFn,
+    ) -> Option<File> {
+        if !mount.path.is_dir() {
+            source.status = SourceStatus::Skipped("not readable, no media?".to_string());
+            return None;
+        }
+
+        #[cfg(windows)]
+        if let (true, Some(letter)) = (mount.is_ntfs(), mount.letter()) {
+            match mft::index_disk(&letter, filter, progress) {
+                Ok(disk) => {
+                    source.status = SourceStatus::Indexed;
+                    return Some(disk);
+                }
+                Err(e) => {
+                    source.status = SourceStatus::WalkedInstead(e.to_string());
+                }
+            }
+        }
+
+        // Without an MFT to read, the disk is walked instead
+        match walk::walk(&mount.path, filter, progress) {
+            Ok(disk) => Some(disk),
+            Err(e) => {
+                source.status = SourceStatus::Failed(e.to_string());
+                None
+            }
+        }
+    }
+
+    /// Indexes the given directories by walking them, with the default rules
+    pub fn walk<P: AsRef<Path> + Sync>(roots: &[P]) -> CaverResult<Self> {
+        Self::walk_with(roots, &Rules::default(), &|_| {})
+    }
+
+    /// Same as [`FileIndex::walk`] with the given rules,
+    /// calling `progress` as the directories are walked
+    pub fn walk_with<P: AsRef<Path> + Sync>(
+        roots: &[P],
+        rules: &Rules,
+        progress: ProgressFn,
+    ) -> CaverResult<Self> {
+        let filter = Filter::new(rules)?;
+
+        let (disks, times): (Vec<_>, Vec<_>) = roots
+            .par_iter()
+            .map(|root| {
+                let start = Instant::now();
+                let mut disk = walk::walk(root.as_ref(), &filter, progress)
+                    .into_caver_result()
+                    .context(root.as_ref().to_owned())?;
+                if rules.archives {
+                    archive::expand(&mut disk, root.as_ref());
+                }
+                Ok((disk, start.elapsed()))
+            })
+            .collect::<CaverResult<Vec<(File, Duration)>>>()?
+            .into_iter()
+            .unzip();
+
+        let mut index = Self::new(disks);
+        for (source, time) in index.sources.iter_mut().zip(times) {
+            source.status = SourceStatus::Walked;
+            source.rules = rules.relevant_to(&source.name);
+            source.index_time = time;
+        }
+        Ok(index)
+    }
+
+    /// Sources indexed with rules that changed since
+    pub fn outdated<'a>(&'a self, rules: &'a Rules) -> impl Iterator<Item = &'a Source> {
+        self.sources
+            .iter()
+            .filter(|source| source.rules != rules.relevant_to(&source.name))
+    }
+
+    /// Indexes again the sources affected by a change of rules, keeping the others.
+    /// Returns how many were indexed again
+    pub fn refresh(&mut self, rules: &Rules, progress: ProgressFn) -> CaverResult<usize> {
+        let filter = Filter::new(rules)?;
+
+        let outdated = self
+            .outdated(rules)
+            .map(|source| Mount {
+                path: PathBuf::from(&source.name),
+                file_system: source.file_system.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        let indexed = outdated
+            .par_iter()
+            .map(|mount| Self::index_mount(mount, rules, &filter, progress))
+            .collect::<Vec<_>>();
+
+        for (source, disk) in indexed {
+            self.disks.retain(|disk| disk.name != source.name);
+            self.disks.extend(disk);
+
+            if let Some(old) = self.sources.iter_mut().find(|s| s.name == source.name) {
+                *old = source;
+            }
+        }
+        self.reindex();
+
+        Ok(outdated.len())
+    }
+
+    /// Sources that failed, were skipped or walked as their MFT couldn't be read
+    pub fn problems(&self) -> impl Iterator<Item = &Source> {
+        self.sources.iter().filter(|source| {
+            matches!(
+                source.status,
+                SourceStatus::Skipped(_) | SourceStatus::Failed(_) | SourceStatus::WalkedInstead(_)
+            )
         })
     }
 
-    pub fn save(&self) -> CaverResult<()> {
-        fs::write(
-            Self::SAVE_PATH.clone(),
-            bincode::serialize(self).into_caver_result()?,
-        )
-        .into_caver_result()?;
+    /// Counts per source, including the ones that were not indexed.
+    /// Visits every file the first time, then kept until the disks change
+    pub fn stats(&self) -> &[SourceStats] {
+        self.stats.get_or_init(|| self.compute_stats())
+    }
 
-        Ok(())
+    fn compute_stats(&self) -> Vec<SourceStats> {
+        self.sources
+            .par_iter()
+            .map(|source| {
+                let disk = self.disks.iter().find(|disk| disk.name == source.name);
+                let usage = disk.map(File::usage).unwrap_or_default();
+                let (deepest, longest_names) = disk.map(extremes).unwrap_or_default();
+
+                let extensions = Extensions::build(disk.map(slice::from_ref).unwrap_or_default());
+                let mut largest_extensions = extensions.iter().cloned().collect::<Vec<_>>();
+                largest_extensions.sort_by_key(|count| Reverse(count.size));
+                largest_extensions.truncate(STATS_TOP);
+
+                SourceStats {
+                    name: source.name.clone(),
+                    status: source.status.clone(),
+                    files: usage.files,
+                    directories: usage.directories,
+                    size: usage.size,
+                    indexed: source.indexed,
+                    index_ms: source.index_time.as_millis() as u64,
+                    index_size: disk
+                        .map(|disk| bincode::serialized_size(disk).unwrap_or_default())
+                        .unwrap_or_default(),
+                    top_extensions: extensions.iter().take(STATS_TOP).cloned().collect(),
+                    largest_extensions,
+                    deepest,
+                    longest_names,
+                }
+            })
+            .collect()
+    }
+
+    /// Bytes the index takes once saved, without the content index
+    pub fn index_size(&self) -> u64 {
+        bincode::serialized_size(self).unwrap_or_default()
+    }
+
+    /// Replaces the saved index at once, see [`db::save`]
+    pub fn save(&self) -> CaverResult<()> {
+        db::save(Path::new(Self::SAVE_PATH), self)
     }
 
     pub fn fetch_from_db() -> CaverResult<Self> {
-        let data = fs::read(Self::SAVE_PATH.clone())?;
-        bincode::deserialize(&data).into_caver_result()
+        Self::fetch_from(Path::new(Self::SAVE_PATH))
     }
 
-    pub fn search(&self, params: SearchParams) -> Vec<(String, PathBuf)> {
-        let res = self
-            .disks
-            .par_iter()
-            .flat_map(|disk| {
-                disk.iter()
-                    .filter_map(|data| {
-                        params
-                            .process(&data)
-                            .then_some((data.0.name.clone(), data.1))
-                    })
-                    .collect::<Vec<_>>()
+    /// Loads an index saved at `path`, e.g. a copy of [`FileIndex::SAVE_PATH`] kept as a snapshot
+    pub fn fetch_from(path: &Path) -> CaverResult<Self> {
+        db::load(path, Self::VERSION)
+    }
+
+    /// The file at `path`, case insensitive on windows
+    pub fn get(&self, path: impl AsRef<Path>) -> Option<Node<'_>> {
+        Some(self.locate(path.as_ref())?.1)
+    }
+
+    /// Same as [`FileIndex::get`] to apply a change, [`File::insert_child`] keeps children sorted.
+    /// Searches visit every file until [`FileIndex::reindex`] is called
+    pub fn get_mut(&mut self, path: impl AsRef<Path>) -> Option<&mut File> {
+        self.names = None;
+        self.stats.take();
+        let path = path.as_ref();
+        let i = self.locate(path)?.0;
+        let rest = strip_root(path, &self.disks[i].name)?;
+
+        rest.components()
+            .try_fold(&mut self.disks[i], |file, component| match component {
+                Component::Normal(name) => file.child_mut(name.to_str()?),
+                _ => Some(file),
             })
-            .collect();
+    }
 
-        res
+    /// The root of every indexed source
+    pub fn disks(&self) -> &[File] {
+        &self.disks
     }
 
-    pub fn search_str(&self, s: &str) -> Vec<(String, PathBuf)> {
+    /// Same as [`FileIndex::get_mut`] for the disks themselves,
+    /// searches visit every file until [`FileIndex::reindex`] is called
+    pub fn disks_mut(&mut self) -> &mut Vec<File> {
+        self.names = None;
+        self.stats.take();
+        &mut self.disks
+    }
+
+    /// Indexes the names and counts the extensions again after changes made through
+    /// [`FileIndex::get_mut`] or [`FileIndex::disks_mut`]
+    pub fn reindex(&mut self) {
+        self.names = Some(Box::new(NameIndex::build(&self.disks)));
+        self.extensions = Extensions::build(&self.disks);
+        self.stats.take();
+    }
+
+    /// The files of each kind, see [`Extensions::by_kind`]
+    pub fn kind_stats(&self) -> Vec<ExtensionCount> {
+        self.extensions.by_kind(&self.kinds)
+    }
+
+    pub fn children(&self, path: impl AsRef<Path>) -> Option<&[File]> {
+        Some(&self.get(path)?.file().children)
+    }
+
+    /// The index of the disk holding `path`, see [`FileIndex::locate`]
+    pub(super) fn disk_of(&self, path: &Path) -> Option<usize> {
+        Some(self.locate(path)?.0)
+    }
+
+    /// The index of the disk holding `path` and its node, the deepest disk wins
+    fn locate(&self, path: &Path) -> Option<(usize, Node<'_>)> {
+        let mut roots = (self.disks.iter().enumerate())
+            .filter_map(|(i, disk)| Some((i, strip_root(path, &disk.name)?)))
+            .collect::<Vec<_>>();
+        roots.sort_by_key(|(_, rest)| rest.components().count());
+
+        roots.into_iter().find_map(|(i, rest)| {
+            let mut ancestors = Vec::new();
+            let file = rest
+                .components()
+                .try_fold(&self.disks[i], |file, component| {
+                    let child = match component {
+                        Component::CurDir => return Some(file),
+                        Component::Normal(name) => file.child(name.to_str()?)?,
+                        _ => return None,
+                    };
+                    ancestors.push(file);
+                    Some(child)
+                })?;
+            Some((i, Node::new(file, ancestors)))
+        })
+    }
+
+    pub fn search(&self, params: SearchParams) -> Vec<(&File, PathBuf)> {
+        self.search_cancellable(params, &AtomicBool::new(false))
+            .unwrap_or_default()
+    }
+
+    /// Same as [`FileIndex::search`] but gives up as soon as `cancel` is set, returning `None`
+    pub fn search_cancellable(
+        &self,
+        params: SearchParams,
+        cancel: &AtomicBool,
+    ) -> Option<Vec<(&File, PathBuf)>> {
+        let params = params.with_kinds(self.kinds.clone());
+        let names =
+            (self.names.as_ref()).and_then(|names| names.search(&self.disks, &params, cancel));
+        let res = names.unwrap_or_else(|| {
+            self.disks
+                .par_iter()
+                .flat_map(|disk| search_disk(disk, &params, cancel))
+                .collect()
+        });
+        let res = params.filter_content(res, self.content.as_ref(), cancel);
+
+        (!cancel.load(Ordering::Relaxed)).then_some(res)
+    }
+
+    pub fn search_str(&self, s: &str) -> Vec<(&File, PathBuf)> {
         let params_parse_start = Instant::now();
         let params = SearchParams::from_str(s);
-        println!(
-            "params parse time {:?}",
-            Instant::now() - params_parse_start
-        );
+        debug!(elapsed = ?params_parse_start.elapsed(), "query parsed");
 
         let search_start = Instant::now();
         let res = self.search(params);
-        println!("search time {:?}", Instant::now() - search_start);
+        debug!(elapsed = ?search_start.elapsed(), results = res.len(), "searched");
         res
     }
 }
+
+/// The files of `disk` matching `params` except for `content`, see [`SearchParams::filter_content`].
+/// Stops early when `cancel` is set
+pub(crate) fn search_disk<'a>(
+    disk: &'a File,
+    params: &SearchParams,
+    cancel: &AtomicBool,
+) -> Vec<(&'a File, PathBuf)> {
+    let mut res = Vec::new();
+    let mut iter = disk.iter();
+    while let Some(entry) = iter.next_entry() {
+        if cancel.load(Ordering::Relaxed) {
+            break;
+        }
+
+        if params.matches_index(&entry) {
+            res.push((entry.file, entry.path.to_path_buf()));
+        }
+        if entry.file.is_dir() && params.prunes(&entry) {
+            iter.skip_children();
+        }
+    }
+    res
+}
+
+/// The rest of `path` if it is under `root`
+pub(crate) fn strip_root<'p>(path: &'p Path, root: &str) -> Option<&'p Path> {
+    let mut components = path.components();
+    for root_component in Path::new(root).components() {
+        let component = components.next()?;
+        let (a, b) = (component.as_os_str(), root_component.as_os_str());
+        if compare_names(&a.to_string_lossy(), &b.to_string_lossy()) != cmp::Ordering::Equal {
+            return None;
+        }
+    }
+    Some(components.as_path())
+}
@@ -0,0 +1,222 @@
+use std::{
+    cmp::Reverse,
+    collections::{BTreeMap, HashMap},
+    path::PathBuf,
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    error::{CaverError, CaverResult},
+};
+
+use super::File;
+
+/// The extension of a file name, without the dot. Names starting with their only dot have none
+pub fn extension(name: &str) -> Option<&str> {
+    match name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() && !extension.is_empty() => Some(extension),
+        _ => None,
+    }
+}
+
+/// Categories of files by extension, matched by `kind<...>`.
+/// Read from `kinds.toml` in the config directory, where each kind lists its extensions,
+/// replacing the built-in list of the same name. The user's kinds come first
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Kinds(Arc<Vec<(String, Vec<String>)>>);
+
+impl Default for Kinds {
+    fn default() -> Self {
+        let kinds: [(&str, &[&str]); 7] = [
+            (
+                "image",
+                &[
+                    "png", "jpg", "jpeg", "gif", "webp", "bmp", "tif", "tiff", "svg", "ico",
+                    "heic", "heif", "avif", "raw", "psd",
+                ],
+            ),
+            (
+                "video",
+                &[
+                    "mp4", "mkv", "avi", "mov", "wmv", "webm", "flv", "m4v", "mpg", "mpeg", "3gp",
+                ],
+            ),
+            (
+                "audio",
+                &[
+                    "mp3", "wav", "flac", "aac", "ogg", "m4a", "wma", "opus", "aiff", "mid",
+                ],
+            ),
+            (
+                "archive",
+                &[
+                    "zip", "rar", "7z", "tar", "gz", "tgz", "bz2", "xz", "zst", "iso", "cab",
+                    "jar", "nupkg",
+                ],
+            ),
+            (
+                "code",
+                &[
+                    "rs", "c", "h", "cpp", "hpp", "cc", "cs", "java", "kt", "py", "js", "ts",
+                    "jsx", "tsx", "go", "rb", "php", "swift", "sh", "ps1", "bat", "lua", "sql",
+                    "html", "css", "json", "toml", "yaml", "yml", "xml",
+                ],
+            ),
+            (
+                "document",
+                &[
+                    "pdf", "doc", "docx", "xls", "xlsx", "ppt", "pptx", "odt", "ods", "odp", "rtf",
+                    "txt", "md", "epub", "csv",
+                ],
+            ),
+            (
+                "executable",
+                &[
+                    "exe", "msi", "dll", "so", "dylib", "app", "apk", "deb", "rpm", "appimage",
+                    "com",
+                ],
+            ),
+        ];
+
+        Self(Arc::new(
+            kinds
+                .into_iter()
+                .map(|(kind, extensions)| {
+                    let extensions = extensions.iter().map(|e| e.to_string()).collect();
+                    (kind.to_string(), extensions)
+                })
+                .collect(),
+        ))
+    }
+}
+
+impl Kinds {
+    /// The built-in kinds with the ones of `s` on top
+    pub fn from_toml(s: &str) -> CaverResult<Self> {
+        let user: BTreeMap<String, Vec<String>> =
+            toml::from_str(s).map_err(CaverError::InvalidKinds)?;
+
+        let mut kinds = (user.into_iter())
+            .map(|(kind, extensions)| {
+                let extensions = (extensions.iter())
+                    .map(|e| e.trim_start_matches('.').to_lowercase())
+                    .collect();
+                (kind, extensions)
+            })
+            .collect::<Vec<_>>();
+        let built_in = Self::default().0.as_ref().clone();
+        for (kind, extensions) in built_in {
+            if !kinds.iter().any(|(name, _)| *name == kind) {
+                kinds.push((kind, extensions));
+            }
+        }
+        Ok(Self(Arc::new(kinds)))
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        config::dir().map(|dir| dir.join("kinds.toml"))
+    }
+
+    /// Loads the user's kinds file, or the built-in kinds if there is none
+    pub fn load() -> CaverResult<Self> {
+        config::load(Self::path(), Self::from_toml)
+    }
+
+    /// The kind of files with `extension`, the first if several list it
+    pub fn of(&self, extension: &str) -> Option<&str> {
+        self.0
+            .iter()
+            .find(|(_, extensions)| extensions.iter().any(|e| e.eq_ignore_ascii_case(extension)))
+            .map(|(kind, _)| kind.as_str())
+    }
+
+    /// Whether `kind` lists `extension`
+    pub fn contains(&self, kind: &str, extension: &str) -> bool {
+        (self.0.iter())
+            .find(|(name, _)| name == kind)
+            .is_some_and(|(_, extensions)| {
+                extensions.iter().any(|e| e.eq_ignore_ascii_case(extension))
+            })
+    }
+}
+
+/// How many files have an extension, or a kind
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExtensionCount {
+    /// Lowercase, or the name of the kind
+    pub name: String,
+    pub files: u64,
+    pub size: u64,
+}
+
+/// Every file extension of an index, lowercase and counted, the most common first
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Extensions {
+    counts: Vec<ExtensionCount>,
+}
+
+impl Extensions {
+    /// The entries of archives take no space of their own and are left out
+    pub fn build(disks: &[File]) -> Self {
+        let mut counts = HashMap::<String, ExtensionCount>::new();
+        for disk in disks {
+            let mut iter = disk.iter();
+            while let Some(entry) = iter.next_entry() {
+                let file = entry.file;
+                if file.is_in_archive() {
+                    iter.skip_children();
+                    continue;
+                }
+                let Some(extension) = extension(&file.name).filter(|_| !file.is_dir()) else {
+                    continue;
+                };
+
+                let extension = extension.to_lowercase();
+                let count = counts
+                    .entry(extension)
+                    .or_insert_with_key(|name| ExtensionCount {
+                        name: name.clone(),
+                        ..Default::default()
+                    });
+                count.files += 1;
+                count.size += file.size;
+            }
+        }
+
+        let mut counts = counts.into_values().collect::<Vec<_>>();
+        counts.sort_by(|a, b| b.files.cmp(&a.files).then_with(|| a.name.cmp(&b.name)));
+        Self { counts }
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ExtensionCount> {
+        self.counts.iter()
+    }
+
+    pub fn get(&self, extension: &str) -> Option<&ExtensionCount> {
+        (self.counts.iter()).find(|count| count.name.eq_ignore_ascii_case(extension))
+    }
+
+    /// The files of each kind, the largest first. Files without a kind are left out
+    pub fn by_kind(&self, kinds: &Kinds) -> Vec<ExtensionCount> {
+        let mut by_kind = BTreeMap::<&str, ExtensionCount>::new();
+        for count in &self.counts {
+            let Some(kind) = kinds.of(&count.name) else {
+                continue;
+            };
+
+            let total = by_kind.entry(kind).or_insert_with(|| ExtensionCount {
+                name: kind.to_string(),
+                ..Default::default()
+            });
+            total.files += count.files;
+            total.size += count.size;
+        }
+
+        let mut by_kind = by_kind.into_values().collect::<Vec<_>>();
+        by_kind.sort_by_key(|count| Reverse(count.size));
+        by_kind
+    }
+}
@@ -0,0 +1,193 @@
+use std::{collections::HashMap, path::PathBuf};
+
+use ntfs_reader::{
+    api::{ntfs_to_unix_time, NtfsAttributeType, FIRST_NORMAL_RECORD, ROOT_RECORD},
+    file::NtfsFile,
+    mft::Mft,
+};
+
+use tracing::debug;
+
+use crate::{
+    disk::DiskLetter,
+    error::{CaverError, CaverResult, IntoCaverResult},
+    progress::{Phase, Progress, ProgressFn, STEP},
+    rules::{Filter, Ignores},
+};
+
+use super::{attributes, sort_files, File, FileKind};
+
+fn file_from_record(record: &NtfsFile, id: u64, name: String) -> File {
+    let mut file = File {
+        name,
+        id,
+        kind: if record.is_directory() {
+            FileKind::Directory
+        } else {
+            FileKind::File
+        },
+        ..Default::default()
+    };
+
+    record.attributes(|attribute| {
+        if attribute.header.type_id == NtfsAttributeType::StandardInformation as u32 {
+            let info = attribute.as_standard_info();
+            file.created = Some(ntfs_to_unix_time(info.creation_time).unix_timestamp());
+            file.modified = Some(ntfs_to_unix_time(info.modification_time).unix_timestamp());
+            file.accessed = Some(ntfs_to_unix_time(info.access_time).unix_timestamp());
+            file.attributes = info.file_attributes & !attributes::CAVER;
+        }
+
+        if attribute.header.type_id == NtfsAttributeType::Data as u32 {
+            file.size = if attribute.header.is_non_resident == 0 {
+                attribute.header_res.value_length as u64
+            } else {
+                attribute.header_nonres.data_size
+            };
+        }
+    });
+
+    file
+}
+
+/// Builds the file tree of a disk by reading its MFT
+pub fn index_disk(
+    diskletter: &DiskLetter,
+    filter: &Filter,
+    progress: ProgressFn,
+) -> CaverResult<File> {
+    let source = diskletter.path_as_str();
+    let report = |phase, done, total| {
+        progress(Progress {
+            source: &source,
+            phase,
+            done,
+            total,
+        })
+    };
+
+    debug!("reading mft");
+    report(Phase::ReadingMft, 0, None);
+    let mft = Mft::new(diskletter.volume()?).into_caver_result()?;
+
+    debug!(records = mft.max_record, "indexing records");
+
+    let mut children_refs = HashMap::new();
+    let mut roots = Vec::new();
+    let mut old_files = (FIRST_NORMAL_RECORD..mft.max_record)
+        .map(|index| {
+            if index % STEP == 0 {
+                report(Phase::Records, index, Some(mft.max_record));
+            }
+
+            if !mft.record_exists(index) {
+                return None;
+            };
+
+            let record = mft.get_record(index)?;
+            let file_name = record.get_best_file_name(&mft)?;
+
+            if !record.is_used() {
+                return None;
+            }
+
+            let parent_index = file_name.parent();
+            if parent_index == ROOT_RECORD {
+                roots.push((index) as usize)
+            } else {
+                children_refs
+                    .entry((parent_index) as usize)
+                    .or_insert(Vec::new())
+                    .push((index) as usize)
+            }
+
+            Some(file_from_record(&record, index, file_name.to_string()))
+        })
+        .collect::<Vec<_>>();
+
+    fn has_child<'a>(
+        old_files: &'a [Option<File>],
+        children: &'a [usize],
+    ) -> impl Fn(&str) -> bool + 'a {
+        move |name| {
+            children.iter().any(|&index| {
+                old_files[index - FIRST_NORMAL_RECORD as usize]
+                    .as_ref()
+                    .is_some_and(|file| file.name == name)
+            })
+        }
+    }
+
+    /// Excluded files are left out with their children, `path` is the one of the parent
+    /// and `ignores` the ignore files that apply in it
+    fn build_tree(
+        index: usize,
+        path: &mut PathBuf,
+        old_files: &mut [Option<File>],
+        children_cache: &HashMap<usize, Vec<usize>>,
+        filter: &Filter,
+        ignores: &Ignores,
+        parent_ignored: bool,
+    ) -> CaverResult<Option<File>> {
+        let mut file = old_files[index - FIRST_NORMAL_RECORD as usize]
+            .take()
+            .ok_or(CaverError::MissingRecord(index as u64))?;
+
+        path.push(&file.name);
+        let kept = filter.keeps(path, &file)
+            && filter.mark_ignored(path, &mut file, ignores, parent_ignored);
+
+        if let Some(children_indices) = children_cache.get(&index).filter(|_| kept) {
+            // Everything under an ignored directory is ignored, its ignore files don't matter
+            let ignores = match file.is_ignored() {
+                true => ignores.clone(),
+                false => ignores.enter(path, has_child(old_files, children_indices)),
+            };
+
+            for &child_index in children_indices {
+                let child = build_tree(
+                    child_index,
+                    path,
+                    old_files,
+                    children_cache,
+                    filter,
+                    &ignores,
+                    file.is_ignored(),
+                )?;
+                file.children.extend(child);
+            }
+            sort_files(&mut file.children);
+        }
+        path.pop();
+
+        Ok(kept.then_some(file))
+    }
+
+    let mut path = PathBuf::from(&source);
+    let ignores = filter
+        .ignores(&path)
+        .enter(&path, has_child(&old_files, &roots));
+    let mut files = Vec::new();
+    for &root_index in &roots {
+        let root = build_tree(
+            root_index,
+            &mut path,
+            &mut old_files,
+            &children_refs,
+            filter,
+            &ignores,
+            false,
+        )?;
+        files.extend(root);
+    }
+    sort_files(&mut files);
+
+    report(Phase::Done, mft.max_record, Some(mft.max_record));
+
+    Ok(File {
+        children: files,
+        name: source,
+        kind: FileKind::Directory,
+        ..Default::default()
+    })
+}
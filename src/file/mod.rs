@@ -1,31 +1,224 @@
+pub(crate) mod archive;
+pub mod diff;
+pub mod dupes;
 pub mod index;
+pub mod kinds;
+#[cfg(windows)]
+mod mft;
+mod names;
+#[cfg(test)]
+mod test;
+pub mod usage;
+mod walk;
 
-use std::path::PathBuf;
+use std::{
+    cmp::Ordering,
+    ops::Deref,
+    path::{Path, PathBuf},
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum FileKind {
+    #[default]
+    File,
+    Directory,
+}
+
+impl FileKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::File => "file",
+            Self::Directory => "directory",
+        }
+    }
+}
+
+/// Bits of [`File::attributes`], the same as windows' `FILE_ATTRIBUTE_*`
+pub mod attributes {
+    pub const READONLY: u32 = 0x1;
+    /// Also set on dot files on other platforms
+    pub const HIDDEN: u32 = 0x2;
+    pub const SYSTEM: u32 = 0x4;
+    pub const SPARSE_FILE: u32 = 0x200;
+    /// The data was moved to offline storage
+    pub const OFFLINE: u32 = 0x1000;
+    /// A cloud file placeholder, e.g. from OneDrive, reading it downloads it
+    pub const RECALL_ON_DATA_ACCESS: u32 = 0x0040_0000;
+    /// Files with these have some or all of their data elsewhere than on the disk,
+    /// reading them is slow, downloads them or fills their holes
+    pub const NOT_ON_DISK: u32 = SPARSE_FILE | OFFLINE | RECALL_ON_DATA_ACCESS;
+    /// Set by caver on files matched by an ignore file, unused by windows
+    pub const IGNORED: u32 = 0x8000_0000;
+    /// Set by caver on the entries of archives and the directories listing them,
+    /// unused by windows
+    pub const IN_ARCHIVE: u32 = 0x4000_0000;
+    /// Set by caver, cleared from the attributes read from windows
+    pub const CAVER: u32 = IGNORED | IN_ARCHIVE;
+}
+
+/// Timestamps are stored as seconds since the unix epoch
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct File {
     pub name: String,
+    pub kind: FileKind,
+    pub size: u64,
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    pub accessed: Option<i64>,
+    pub attributes: u32,
+    /// MFT record number or inode, the same across renames and moves, 0 if unknown
+    pub id: u64,
+    /// Sorted with [`compare_names`], see [`File::insert_child`]
     pub children: Vec<File>,
 }
 
 impl File {
-    pub fn iter(&self) -> FileIterator {
+    pub fn is_dir(&self) -> bool {
+        self.kind == FileKind::Directory
+    }
+
+    /// Listed from an archive, see [`Rules::archives`](crate::rules::Rules::archives)
+    pub fn is_in_archive(&self) -> bool {
+        self.attributes & attributes::IN_ARCHIVE != 0
+    }
+
+    pub fn is_hidden(&self) -> bool {
+        self.attributes & attributes::HIDDEN != 0
+    }
+
+    pub fn is_system(&self) -> bool {
+        self.attributes & attributes::SYSTEM != 0
+    }
+
+    /// Matched by a `.gitignore`, `.ignore` or the global ignore file
+    pub fn is_ignored(&self) -> bool {
+        self.attributes & attributes::IGNORED != 0
+    }
+
+    pub fn iter(&self) -> FileIterator<'_> {
         FileIterator::new(self)
     }
+
+    pub fn child(&self, name: &str) -> Option<&File> {
+        let i = self.find_child(name).ok()?;
+        Some(&self.children[i])
+    }
+
+    pub fn child_mut(&mut self, name: &str) -> Option<&mut File> {
+        let i = self.find_child(name).ok()?;
+        Some(&mut self.children[i])
+    }
+
+    /// Keeps the children sorted, returns the child that had the same name
+    pub fn insert_child(&mut self, file: File) -> Option<File> {
+        match self.find_child(&file.name) {
+            Ok(i) => Some(std::mem::replace(&mut self.children[i], file)),
+            Err(i) => {
+                self.children.insert(i, file);
+                None
+            }
+        }
+    }
+
+    pub fn remove_child(&mut self, name: &str) -> Option<File> {
+        let i = self.find_child(name).ok()?;
+        Some(self.children.remove(i))
+    }
+
+    fn find_child(&self, name: &str) -> Result<usize, usize> {
+        self.children
+            .binary_search_by(|child| compare_names(&child.name, name))
+    }
+}
+
+/// The order of [`File::children`], case insensitive on windows like its file systems
+pub fn compare_names(a: &str, b: &str) -> Ordering {
+    if cfg!(windows) {
+        (a.chars().flat_map(char::to_lowercase)).cmp(b.chars().flat_map(char::to_lowercase))
+    } else {
+        a.cmp(b)
+    }
+}
+
+pub(crate) fn sort_files(files: &mut [File]) {
+    files.sort_unstable_by(|a, b| compare_names(&a.name, &b.name));
+}
+
+/// A file of a [`FileIndex`](index::FileIndex) with the directories above it
+#[derive(Clone)]
+pub struct Node<'a> {
+    file: &'a File,
+    /// From the root down to the parent of `file`
+    ancestors: Vec<&'a File>,
+}
+
+impl<'a> Node<'a> {
+    pub(crate) fn new(file: &'a File, ancestors: Vec<&'a File>) -> Self {
+        Self { file, ancestors }
+    }
+
+    pub fn file(&self) -> &'a File {
+        self.file
+    }
+
+    pub fn ancestors(&self) -> &[&'a File] {
+        &self.ancestors
+    }
+
+    /// The root of a disk is at depth 0
+    pub fn depth(&self) -> usize {
+        self.ancestors.len()
+    }
+
+    pub fn path(&self) -> PathBuf {
+        let mut path = PathBuf::new();
+        for file in self.ancestors.iter().chain([&self.file]) {
+            path.push(&file.name);
+        }
+        path
+    }
+
+    pub fn parent(&self) -> Option<Node<'a>> {
+        let (&file, ancestors) = self.ancestors.split_last()?;
+        Some(Node {
+            file,
+            ancestors: ancestors.to_vec(),
+        })
+    }
+
+    pub fn child(&self, name: &str) -> Option<Node<'a>> {
+        self.file.child(name).map(|file| self.descend(file))
+    }
+
+    pub fn children(&self) -> impl Iterator<Item = Node<'a>> + '_ {
+        self.file.children.iter().map(|file| self.descend(file))
+    }
+
+    fn descend(&self, file: &'a File) -> Node<'a> {
+        let mut ancestors = self.ancestors.clone();
+        ancestors.push(self.file);
+        Node { file, ancestors }
+    }
+}
+
+impl Deref for Node<'_> {
+    type Target = File;
+
+    fn deref(&self) -> &File {
+        self.file
+    }
 }
 
-pub trait IsValidWindowsFileName {
+pub(crate) trait IsValidWindowsFileName {
     fn is_valid_windows_file_name(&self) -> bool;
 }
 
 impl IsValidWindowsFileName for char {
     fn is_valid_windows_file_name(&self) -> bool {
-        match self {
-            '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => false,
-            _ => true,
-        }
+        !matches!(self, '\\' | '/' | ':' | '*' | '?' | '"' | '<' | '>' | '|')
     }
 }
 
@@ -41,6 +234,18 @@ impl IsValidWindowsFileName for str {
 pub struct FileIterator<'a> {
     path: PathBuf,
     stack: Vec<std::slice::Iter<'a, File>>,
+    /// The root and the directories leading to the last yielded file, then that file
+    files: Vec<&'a File>,
+}
+
+/// A file yielded by [`FileIterator::next_entry`], borrowing the iterator's path
+pub struct Entry<'i, 'a> {
+    pub file: &'a File,
+    /// The children of the root are at depth 1
+    pub depth: usize,
+    pub path: &'i Path,
+    /// From the root down to the parent of `file`
+    pub parents: &'i [&'a File],
 }
 
 impl<'a> FileIterator<'a> {
@@ -50,24 +255,47 @@ impl<'a> FileIterator<'a> {
         Self {
             path,
             stack: vec![root.children.iter()],
+            files: vec![root],
         }
     }
-}
 
-impl<'a> Iterator for FileIterator<'a> {
-    type Item = (&'a File, PathBuf);
-
-    fn next(&mut self) -> Option<Self::Item> {
+    /// Same as [`Iterator::next`] without cloning the path
+    pub fn next_entry(&mut self) -> Option<Entry<'_, 'a>> {
         while let Some(top) = self.stack.last_mut() {
             if let Some(file) = top.next() {
                 self.path.push(&file.name);
                 self.stack.push(file.children.iter());
-                return Some((file, self.path.clone()));
+                self.files.push(file);
+
+                let (file, parents) = self.files.split_last().unwrap();
+                return Some(Entry {
+                    file,
+                    depth: parents.len(),
+                    path: &self.path,
+                    parents,
+                });
             } else {
                 self.path.pop();
                 self.stack.pop();
+                self.files.pop();
             }
         }
         None
     }
+
+    /// Don't visit the children of the last yielded file
+    pub fn skip_children(&mut self) {
+        if let Some(top) = self.stack.last_mut() {
+            *top = [].iter();
+        }
+    }
+}
+
+impl<'a> Iterator for FileIterator<'a> {
+    type Item = (&'a File, PathBuf);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry()
+            .map(|entry| (entry.file, entry.path.to_path_buf()))
+    }
 }
@@ -0,0 +1,182 @@
+use std::{
+    collections::HashMap,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+
+use crate::search::{trigram::trigrams, SearchExpr, SearchExprValue, SearchOperator, SearchParams};
+
+use super::{Entry, File};
+
+/// Parent of the disks
+const NO_PARENT: u32 = u32::MAX;
+
+/// The trigrams of every file name, to find the files whose name holds a value without
+/// visiting the others. Files are numbered in the order [`File::iter`] visits them, after their disk
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub(crate) struct NameIndex {
+    /// The number of the directory holding each file, [`NO_PARENT`] for the disks
+    parents: Vec<u32>,
+    /// Where each file is in the children of its parent, or in the disks
+    slots: Vec<u32>,
+    /// The files whose name holds each trigram, in ascending order
+    postings: HashMap<u64, Vec<u32>>,
+}
+
+/// The numbers in both sorted lists
+fn intersect(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let (small, large) = if a.len() <= b.len() { (a, b) } else { (b, a) };
+    let mut rest = large;
+    small
+        .iter()
+        .copied()
+        .filter(|n| match rest.binary_search(n) {
+            Ok(i) => {
+                rest = &rest[i + 1..];
+                true
+            }
+            Err(i) => {
+                rest = &rest[i..];
+                false
+            }
+        })
+        .collect()
+}
+
+/// The numbers in either sorted list, sorted
+fn union(a: &[u32], b: &[u32]) -> Vec<u32> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let n = a[i].min(b[j]);
+        out.push(n);
+        i += (a[i] == n) as usize;
+        j += (b[j] == n) as usize;
+    }
+    out.extend_from_slice(&a[i..]);
+    out.extend_from_slice(&b[j..]);
+    out
+}
+
+impl NameIndex {
+    pub(crate) fn build(disks: &[File]) -> Self {
+        let mut index = Self::default();
+        for (slot, disk) in disks.iter().enumerate() {
+            index.add(disk, NO_PARENT, slot);
+        }
+        index
+    }
+
+    fn add(&mut self, file: &File, parent: u32, slot: usize) {
+        let n = self.parents.len() as u32;
+        self.parents.push(parent);
+        self.slots.push(slot as u32);
+
+        // Disks are never results
+        if parent != NO_PARENT {
+            for trigram in trigrams(&file.name) {
+                self.postings.entry(trigram).or_default().push(n);
+            }
+        }
+        for (slot, child) in file.children.iter().enumerate() {
+            self.add(child, n, slot);
+        }
+    }
+
+    /// The files whose name may match `expr`, sorted.
+    /// `None` if it can't tell without every name, e.g. for values shorter than 3 characters
+    fn candidates(&self, expr: &SearchExpr) -> Option<Vec<u32>> {
+        if expr.inverted {
+            return None;
+        }
+
+        match &expr.expr {
+            SearchExprValue::Value(value) => {
+                let mut lists = trigrams(value)
+                    .into_iter()
+                    .map(|trigram| self.postings.get(&trigram).map(Vec::as_slice))
+                    .collect::<Option<Vec<_>>>()
+                    .unwrap_or_else(|| vec![&[]]);
+                lists.sort_by_key(|list| list.len());
+
+                let (first, rest) = lists.split_first()?;
+                Some(
+                    rest.iter()
+                        .fold(first.to_vec(), |found, list| intersect(&found, list)),
+                )
+            }
+            SearchExprValue::Operation(op) => {
+                let (lhs, rhs) = (self.candidates(&op.lhs), self.candidates(&op.rhs));
+                match op.operation {
+                    SearchOperator::And => match (lhs, rhs) {
+                        (Some(lhs), Some(rhs)) => Some(intersect(&lhs, &rhs)),
+                        (found, None) | (None, found) => found,
+                    },
+                    SearchOperator::Or => Some(union(&lhs?, &rhs?)),
+                }
+            }
+        }
+    }
+
+    /// The file numbered `n` and the files from its disk down to its parent
+    fn resolve<'a>(&self, disks: &'a [File], n: u32) -> (&'a File, Vec<&'a File>) {
+        let mut slots = Vec::new();
+        let mut n = n as usize;
+        loop {
+            slots.push(self.slots[n] as usize);
+            match self.parents[n] {
+                NO_PARENT => break,
+                parent => n = parent as usize,
+            }
+        }
+
+        let mut slots = slots.into_iter().rev();
+        let mut file = &disks[slots.next().unwrap()];
+        let mut parents = Vec::new();
+        for slot in slots {
+            parents.push(file);
+            file = &file.children[slot];
+        }
+        (file, parents)
+    }
+
+    /// Same as searching every disk, looking only at the files whose name may match.
+    /// `None` if the name in `params` doesn't narrow them down
+    pub(crate) fn search<'a>(
+        &self,
+        disks: &'a [File],
+        params: &SearchParams,
+        cancel: &AtomicBool,
+    ) -> Option<Vec<(&'a File, PathBuf)>> {
+        let candidates = self.candidates(params.name()?)?;
+
+        Some(
+            candidates
+                .into_par_iter()
+                .filter_map(|n| {
+                    if cancel.load(Ordering::Relaxed) {
+                        return None;
+                    }
+
+                    let (file, parents) = self.resolve(disks, n);
+                    let mut path = PathBuf::new();
+                    for parent in &parents {
+                        path.push(&parent.name);
+                    }
+                    path.push(&file.name);
+
+                    let entry = Entry {
+                        file,
+                        depth: parents.len(),
+                        path: &path,
+                        parents: &parents,
+                    };
+                    params.matches_index(&entry).then_some((file, path))
+                })
+                .collect(),
+        )
+    }
+}
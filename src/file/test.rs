@@ -0,0 +1,505 @@
+use std::{fs, sync::atomic::AtomicBool};
+
+use crate::{
+    error::{CaverError, Context},
+    rules::{Filter, Rules},
+    search::SearchParams,
+    test_util::{names, relative_paths, temp_tree},
+};
+
+use super::{
+    index::{search_disk, FileIndex, SourceStatus},
+    kinds::Kinds,
+    names::NameIndex,
+    walk,
+};
+
+#[test]
+fn walked_stats() {
+    let root = temp_tree("stats");
+    let fi = FileIndex::walk(&[&root]).unwrap();
+
+    assert_eq!(fi.problems().count(), 0);
+
+    let stats = fi.stats();
+    assert_eq!(stats.len(), 1);
+    assert_eq!(stats[0].status, SourceStatus::Walked);
+    assert_eq!(stats[0].files, 2);
+    assert_eq!(stats[0].directories, 1);
+    assert_eq!(stats[0].size, 7);
+
+    assert!(stats[0].indexed.is_some());
+    assert!(stats[0].index_size > 0 && stats[0].index_size < fi.index_size());
+    assert_eq!(stats[0].top_extensions[0].name, "txt");
+    assert_eq!(stats[0].top_extensions[0].files, 2);
+    assert!(stats[0].deepest.as_ref().unwrap().ends_with("sub/b.txt"));
+    assert_eq!(stats[0].longest_names, ["a.txt", "b.txt", "sub"]);
+}
+
+#[cfg(unix)]
+#[test]
+fn non_unicode_names() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let root = temp_tree("non_unicode_names");
+    fs::write(root.join(OsStr::from_bytes(b"caf\xe9.txt")), "").unwrap();
+    let fi = FileIndex::walk(&[&root]).unwrap();
+
+    assert_eq!(names(&fi, ".txt"), ["a.txt", "b.txt"]);
+}
+
+#[test]
+fn missing_root() {
+    let root = std::env::temp_dir().join("caver-missing-root");
+    let Err(e) = FileIndex::walk(&[&root]) else {
+        panic!("walked a missing directory");
+    };
+
+    assert!(matches!(&e, CaverError::WithContext(Context::Path(path), _) if *path == root));
+    assert!(matches!(e.root(), CaverError::IOError(_)));
+}
+
+#[test]
+fn refresh_on_new_rules() {
+    let root = temp_tree("rules");
+    let mut fi = FileIndex::walk(&[&root]).unwrap();
+    assert_eq!(fi.stats()[0].files, 2);
+
+    let rules = Rules {
+        exclude: vec!["sub".to_string()],
+        ..Default::default()
+    };
+    assert_eq!(fi.outdated(&rules).count(), 1);
+    assert_eq!(fi.refresh(&rules, &|_| {}).unwrap(), 1);
+
+    assert_eq!(fi.outdated(&rules).count(), 0);
+    assert_eq!(fi.sources[0].status, SourceStatus::Walked);
+    let names = fi.disks()[0].iter().map(|(file, _)| file.name.clone());
+    assert_eq!(names.collect::<Vec<_>>(), ["a.txt"]);
+    // Counted again
+    assert_eq!(fi.stats()[0].files, 1);
+}
+
+#[test]
+fn ignore_files() {
+    let root = temp_tree("ignore");
+    for dir in ["target/debug", "build", "src/build", "src/gen"] {
+        fs::create_dir_all(root.join(dir)).unwrap();
+    }
+    for file in [
+        "a.log",
+        "keep.log",
+        "target/debug/caver",
+        "src/main.rs",
+        "src/gen/x.rs",
+    ] {
+        fs::write(root.join(file), "").unwrap();
+    }
+    fs::write(
+        root.join(".gitignore"),
+        "target/\n*.log\n!keep.log\n/build\n",
+    )
+    .unwrap();
+    fs::write(root.join("src").join(".ignore"), "gen\n").unwrap();
+
+    // Instead of the user's own global ignore file
+    let global = root.with_extension("ignore");
+    fs::write(&global, "sub/\n").unwrap();
+    let walk = |rules: &Rules| {
+        let filter = Filter::new(rules)
+            .unwrap()
+            .with_global_ignore(Some(global.clone()));
+        FileIndex::new(vec![walk::walk(&root, &filter, &|_| {}).unwrap()])
+    };
+
+    let fi = walk(&Rules::default());
+    let search = |query| relative_paths(&fi, &root, query);
+
+    // Anchored `/build` doesn't match `src/build`
+    assert_eq!(
+        search("ignored<no>"),
+        [
+            ".gitignore",
+            "a.txt",
+            "keep.log",
+            "src",
+            "src/.ignore",
+            "src/build",
+            "src/main.rs"
+        ]
+    );
+    assert_eq!(
+        search("ignored<yes>"),
+        [
+            "a.log",
+            "build",
+            "src/gen",
+            "src/gen/x.rs",
+            "sub",
+            "sub/b.txt",
+            "target",
+            "target/debug",
+            "target/debug/caver"
+        ]
+    );
+
+    let rules = Rules {
+        gitignore: true,
+        ..Default::default()
+    };
+    let fi = walk(&rules);
+    fs::remove_file(&global).unwrap();
+    assert_eq!(fi.stats()[0].files + fi.stats()[0].directories, 7);
+}
+
+#[test]
+fn skip_children() {
+    let root = temp_tree("skip");
+    let fi = FileIndex::walk(&[&root]).unwrap();
+
+    let mut iter = fi.disks()[0].iter();
+    let mut visited = Vec::new();
+    while let Some(entry) = iter.next_entry() {
+        assert_eq!(entry.path, root.join(&entry.file.name));
+        assert_eq!(entry.parents[0].name, fi.disks()[0].name);
+        visited.push((entry.file.name.clone(), entry.depth));
+        if entry.file.name == "sub" {
+            iter.skip_children();
+        }
+    }
+    visited.sort();
+    assert_eq!(visited, [("a.txt".to_string(), 1), ("sub".to_string(), 1)]);
+
+    let results = fi.search_str("path<?sub>");
+    let names = results.iter().map(|(file, _)| file.name.as_str());
+    assert_eq!(names.collect::<Vec<_>>(), ["a.txt"]);
+}
+
+#[test]
+fn tree_fields() {
+    let root = temp_tree("fields");
+    fs::create_dir_all(root.join("sub").join("deep")).unwrap();
+    fs::write(root.join("sub").join("deep").join("c.txt"), "").unwrap();
+
+    let fi = FileIndex::walk(&[&root]).unwrap();
+    let search = |query| names(&fi, query);
+
+    assert_eq!(search("depth<2>"), ["b.txt", "deep"]);
+    assert_eq!(search("depth<..1>"), ["a.txt", "sub"]);
+    assert_eq!(search(".txt depth<2..>"), ["b.txt", "c.txt"]);
+    assert_eq!(search("parent<sub>"), ["b.txt", "deep"]);
+    assert_eq!(search("ancestor<sub> .txt"), ["b.txt", "c.txt"]);
+    assert_eq!(search("child<b.txt | c.txt>"), ["deep", "sub"]);
+}
+
+#[test]
+fn lookup() {
+    let root = temp_tree("lookup");
+    let mut fi = FileIndex::walk(&[&root]).unwrap();
+
+    let b = fi.get(root.join("sub").join("b.txt")).unwrap();
+    assert_eq!(b.size, 2);
+    assert_eq!(b.depth(), 2);
+    assert_eq!(b.path(), root.join("sub").join("b.txt"));
+    assert_eq!(b.parent().unwrap().name, "sub");
+    assert!(fi.get(root.join("sub").join("c.txt")).is_none());
+
+    let names = |fi: &FileIndex| {
+        let children = fi.children(&root).unwrap();
+        children
+            .iter()
+            .map(|file| file.name.clone())
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(names(&fi), ["a.txt", "sub"]);
+
+    let dir = fi.get_mut(&root).unwrap();
+    dir.insert_child(super::File {
+        name: "0.txt".to_string(),
+        ..Default::default()
+    });
+    assert_eq!(names(&fi), ["0.txt", "a.txt", "sub"]);
+}
+
+#[test]
+fn disk_usage() {
+    let root = temp_tree("du");
+    let fi = FileIndex::walk(&[&root]).unwrap();
+
+    let dirs = fi.du(None, 1).unwrap();
+    let dirs = dirs
+        .iter()
+        .map(|dir| {
+            (
+                dir.file.name.as_str(),
+                dir.depth,
+                dir.usage.size,
+                dir.usage.files,
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        dirs,
+        [(fi.disks()[0].name.as_str(), 0, 7, 2), ("sub", 1, 2, 1)]
+    );
+
+    let sub = fi.du(Some(&root.join("sub")), 0).unwrap();
+    assert_eq!(sub[0].path, root.join("sub"));
+    assert!(matches!(
+        fi.du(Some(&root.join("missing")), 0),
+        Err(CaverError::NotIndexed(_))
+    ));
+}
+
+#[test]
+fn dupes() {
+    let root = temp_tree("dupes");
+    fs::write(root.join("sub").join("c.txt"), "hello").unwrap();
+    fs::write(root.join("d.txt"), "hellp").unwrap();
+    let big = vec![b'x'; 10_000];
+    fs::write(root.join("big1"), &big).unwrap();
+    fs::write(root.join("big2"), &big).unwrap();
+    let mut other = big.clone();
+    other[9_999] = b'y';
+    fs::write(root.join("big3"), other).unwrap();
+
+    let fi = FileIndex::walk(&[&root]).unwrap();
+    let groups = fi.dupes(SearchParams::from_str(""));
+    let scoped = fi.dupes(SearchParams::from_str(".txt"));
+
+    let names = |group: &super::dupes::DupeGroup| {
+        let names = group.files.iter().map(|(file, _)| file.name.clone());
+        names.collect::<Vec<_>>()
+    };
+    assert_eq!(groups.len(), 2);
+    assert_eq!(names(&groups[0]), ["big1", "big2"]);
+    assert_eq!(groups[0].wasted(), 10_000);
+    assert_eq!(names(&groups[1]), ["a.txt", "c.txt"]);
+    assert_eq!(scoped.len(), 1);
+}
+
+#[cfg(unix)]
+#[test]
+fn dupes_hard_links() {
+    let root = temp_tree("dupes_hard_links");
+    fs::hard_link(root.join("a.txt"), root.join("sub").join("link.txt")).unwrap();
+    let fi = FileIndex::walk(&[&root]).unwrap();
+    let groups = fi.dupes(SearchParams::from_str(""));
+
+    assert!(groups.is_empty());
+}
+
+#[cfg(unix)]
+#[test]
+fn diff() {
+    use super::diff::ChangeKind;
+
+    let root = temp_tree("diff");
+    fs::write(root.join("c.txt"), "x").unwrap();
+    fs::write(root.join("gone.txt"), "").unwrap();
+    fs::hard_link(root.join("gone.txt"), root.join("gone-link.txt")).unwrap();
+    let old = FileIndex::walk(&[&root]).unwrap();
+
+    fs::rename(root.join("a.txt"), root.join("renamed.txt")).unwrap();
+    fs::rename(root.join("sub"), root.join("moved")).unwrap();
+    fs::write(root.join("c.txt"), "xyz").unwrap();
+    fs::write(root.join("new.txt"), "").unwrap();
+    fs::remove_file(root.join("gone.txt")).unwrap();
+    fs::remove_file(root.join("gone-link.txt")).unwrap();
+    let new = FileIndex::walk(&[&root]).unwrap();
+
+    let changes = old.diff(&new, &SearchParams::from_str(""));
+    let changes = changes
+        .iter()
+        .map(|change| {
+            let path = change.path.strip_prefix(&root).unwrap();
+            let from = change
+                .from
+                .as_ref()
+                .map(|from| from.strip_prefix(&root).unwrap());
+            (
+                change.kind,
+                path.to_str().unwrap(),
+                from.and_then(|from| from.to_str()),
+            )
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(
+        changes,
+        [
+            (ChangeKind::Resized, "c.txt", None),
+            (ChangeKind::Removed, "gone-link.txt", None),
+            (ChangeKind::Removed, "gone.txt", None),
+            (ChangeKind::Moved, "moved", Some("sub")),
+            (ChangeKind::Added, "new.txt", None),
+            (ChangeKind::Moved, "renamed.txt", Some("a.txt")),
+        ]
+    );
+    assert_eq!(old.diff(&new, &SearchParams::from_str("c.txt")).len(), 1);
+}
+
+#[test]
+fn name_index() {
+    let root = temp_tree("names");
+    fs::create_dir_all(root.join("sub").join("deep")).unwrap();
+    fs::write(root.join("sub").join("deep").join("abc.txt"), "").unwrap();
+    fs::write(root.join("abcd.rs"), "").unwrap();
+    let fi = FileIndex::walk(&[&root]).unwrap();
+
+    let names = NameIndex::build(fi.disks());
+    let cancel = AtomicBool::new(false);
+    let search = |query| {
+        let params = SearchParams::from_str(query);
+        let scanned = (fi.disks().iter())
+            .flat_map(|disk| search_disk(disk, &params, &cancel))
+            .map(|(_, path)| path)
+            .collect::<Vec<_>>();
+        let indexed = (names.search(fi.disks(), &params, &cancel))
+            .map(|res| res.into_iter().map(|(_, path)| path).collect::<Vec<_>>());
+        (scanned, indexed)
+    };
+
+    for query in [
+        "abc",
+        "txt",
+        "abc .txt",
+        "abcd | b.t",
+        "abc ?.rs",
+        "txt depth<3>",
+    ] {
+        let (scanned, indexed) = search(query);
+        assert_eq!(Some(scanned), indexed, "{query}");
+    }
+    assert_eq!(search("abc").0.len(), 2);
+    assert_eq!(search("xyz").1, Some(vec![]));
+
+    // Values too short or negated can't narrow the search down
+    for query in ["ab", "?abc", "abc | b", "path<sub>"] {
+        assert_eq!(search(query).1, None, "{query}");
+    }
+
+    // The positions it kept are dropped with changes to the disks
+    let mut fi = fi;
+    fi.disks_mut().clear();
+    assert!(fi.search_str("abc").is_empty());
+}
+
+#[test]
+fn kinds() {
+    let root = temp_tree("kinds");
+    fs::write(root.join("photo.PNG"), "png").unwrap();
+    fs::write(root.join("photo.png.bak"), "").unwrap();
+    fs::write(root.join(".png"), "").unwrap();
+    fs::create_dir_all(root.join("album.png")).unwrap();
+    let mut fi = FileIndex::walk(&[&root]).unwrap();
+
+    assert_eq!(names(&fi, "ext<png>"), ["photo.PNG"]);
+    assert_eq!(
+        names(&fi, "ext<.bak | txt>"),
+        ["a.txt", "b.txt", "photo.png.bak"]
+    );
+    assert_eq!(names(&fi, "kind<image>"), ["photo.PNG"]);
+    assert_eq!(names(&fi, "kind<document> depth<1>"), ["a.txt"]);
+
+    let txt = fi.extensions.get("TXT").unwrap();
+    assert_eq!((txt.files, txt.size), (2, 7));
+    assert_eq!(fi.extensions.iter().next().unwrap().name, "txt");
+    assert!(fi.extensions.get("").is_none());
+
+    fi.kinds = Kinds::from_toml("image = [\"bak\"]\nnotes = [\".TXT\"]").unwrap();
+    assert_eq!(names(&fi, "kind<image>"), ["photo.png.bak"]);
+    assert_eq!(names(&fi, "kind<notes>"), ["a.txt", "b.txt"]);
+    assert!(fi.kinds.contains("code", "rs"));
+
+    let stats = fi.kind_stats();
+    let stats = (stats.iter())
+        .map(|kind| (kind.name.as_str(), kind.files, kind.size))
+        .collect::<Vec<_>>();
+    assert_eq!(stats, [("notes", 2, 7), ("image", 1, 0)]);
+
+    assert!(matches!(
+        Kinds::from_toml("image = 1"),
+        Err(CaverError::InvalidKinds(_))
+    ));
+}
+
+#[test]
+fn archives() {
+    use std::io::Write;
+
+    use flate2::{write::GzEncoder, Compression};
+    use zip::{write::SimpleFileOptions, ZipWriter};
+
+    let root = temp_tree("archives");
+    let mut zip = ZipWriter::new(fs::File::create(root.join("pkg.zip")).unwrap());
+    zip.start_file("lib/config.json", SimpleFileOptions::default())
+        .unwrap();
+    zip.write_all(br#"{"key": 1}"#).unwrap();
+    zip.add_directory("empty/", SimpleFileOptions::default())
+        .unwrap();
+    zip.finish().unwrap();
+
+    let gz = GzEncoder::new(
+        fs::File::create(root.join("src.tar.gz")).unwrap(),
+        Compression::default(),
+    );
+    let mut tar = tar::Builder::new(gz);
+    let mut header = tar::Header::new_gnu();
+    header.set_size(9);
+    header.set_mode(0o644);
+    header.set_mtime(1_700_000_000);
+    tar.append_data(&mut header, "./src/main.rs", &b"fn main()"[..])
+        .unwrap();
+    tar.into_inner().unwrap().finish().unwrap();
+
+    // Its data is read at the offset of each entry, under a directory ending like a listing
+    fs::create_dir_all(root.join("old!")).unwrap();
+    let mut tar = tar::Builder::new(fs::File::create(root.join("old!").join("data.tar")).unwrap());
+    for (name, data) in [("a.txt", "alpha"), ("b.txt", "beta")] {
+        let mut header = tar::Header::new_gnu();
+        header.set_size(data.len() as u64);
+        header.set_mode(0o644);
+        tar.append_data(&mut header, name, data.as_bytes()).unwrap();
+    }
+    tar.finish().unwrap();
+
+    let rules = Rules {
+        archives: true,
+        ..Default::default()
+    };
+    let fi = FileIndex::walk_with(&[&root], &rules, &|_| {}).unwrap();
+    let plain = FileIndex::walk(&[&root]).unwrap();
+    let paths = |query| {
+        (fi.search_str(query).into_iter())
+            .map(|(_, path)| path)
+            .collect::<Vec<_>>()
+    };
+
+    let config = root.join("pkg.zip!").join("lib").join("config.json");
+    let main = root.join("src.tar.gz!").join("src").join("main.rs");
+    assert_eq!(paths("name<config.json>"), vec![config.clone()]);
+    assert_eq!(paths("content<key>"), vec![config.clone()]);
+    assert_eq!(paths("main content<fn main>"), vec![main.clone()]);
+    let data = root.join("old!").join("data.tar!");
+    assert_eq!(paths("content<alpha>"), vec![data.join("a.txt")]);
+    assert_eq!(paths("content<beta>"), vec![data.join("b.txt")]);
+    assert!(plain.search_str("config.json").is_empty());
+
+    let entry = fi.get(&main).unwrap();
+    assert!(entry.file().is_in_archive());
+    assert_eq!(entry.file().modified, Some(1_700_000_000));
+    assert!(fi
+        .get(root.join("pkg.zip!").join("empty"))
+        .unwrap()
+        .file()
+        .is_dir());
+
+    // Entries take no space of their own on the disk
+    assert_eq!(fi.stats()[0].size, plain.stats()[0].size);
+    assert_eq!(
+        fi.stats()[0].top_extensions,
+        plain.stats()[0].top_extensions
+    );
+    assert_eq!(fi.stats()[0].deepest, plain.stats()[0].deepest);
+    assert_eq!(fi.kind_stats(), plain.kind_stats());
+}
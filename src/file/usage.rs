@@ -0,0 +1,125 @@
+use std::{
+    cmp::Reverse,
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+
+use crate::error::{CaverError, CaverResult};
+
+use super::{index::FileIndex, File};
+
+/// What is under a directory, the directory itself excluded
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Usage {
+    pub size: u64,
+    pub files: u64,
+    pub directories: u64,
+}
+
+impl Usage {
+    fn add(&mut self, other: Usage) {
+        self.size += other.size;
+        self.files += other.files;
+        self.directories += other.directories;
+    }
+}
+
+/// A directory listed by [`FileIndex::du`]
+#[derive(Debug, Clone)]
+pub struct DirUsage<'a> {
+    pub file: &'a File,
+    pub path: PathBuf,
+    /// The directory `du` was asked about is at depth 0
+    pub depth: usize,
+    pub usage: Usage,
+}
+
+impl File {
+    /// Sums the sizes of everything under the directory.
+    /// The entries of archives only count under the directories listing them
+    pub fn usage(&self) -> Usage {
+        let mut usage = Usage::default();
+        for child in self.counted_children() {
+            usage.add(child.own_usage());
+            usage.add(child.usage());
+        }
+        usage
+    }
+
+    fn counted_children(&self) -> impl Iterator<Item = &File> {
+        (self.children.iter()).filter(|child| self.is_in_archive() || !child.is_in_archive())
+    }
+
+    /// The file alone, as counted in the usage of its parent
+    fn own_usage(&self) -> Usage {
+        Usage {
+            size: self.size,
+            files: (!self.is_dir()) as u64,
+            directories: self.is_dir() as u64,
+        }
+    }
+}
+
+/// Pushes the directories under `file` down to `max_depth` into `out`, returns the usage of `file`
+fn collect<'a>(
+    file: &'a File,
+    path: &mut PathBuf,
+    depth: usize,
+    max_depth: usize,
+    out: &mut Vec<DirUsage<'a>>,
+) -> Usage {
+    if depth >= max_depth {
+        let usage = file.usage();
+        out.push(DirUsage {
+            file,
+            path: path.clone(),
+            depth,
+            usage,
+        });
+        return usage;
+    }
+
+    let mut usage = Usage::default();
+    for child in file.counted_children() {
+        usage.add(child.own_usage());
+        if child.is_dir() {
+            path.push(&child.name);
+            usage.add(collect(child, path, depth + 1, max_depth, out));
+            path.pop();
+        }
+    }
+
+    out.push(DirUsage {
+        file,
+        path: path.clone(),
+        depth,
+        usage,
+    });
+    usage
+}
+
+impl FileIndex {
+    /// The directories from `path` (every disk if `None`) down to `depth` levels under it,
+    /// the largest first
+    pub fn du(&self, path: Option<&Path>, depth: usize) -> CaverResult<Vec<DirUsage<'_>>> {
+        let roots = match path {
+            Some(path) => {
+                let node = self
+                    .get(path)
+                    .ok_or_else(|| CaverError::NotIndexed(path.to_path_buf()))?;
+                vec![(node.file(), node.path())]
+            }
+            None => (self.disks().iter())
+                .map(|disk| (disk, PathBuf::from(&disk.name)))
+                .collect(),
+        };
+
+        let mut out = Vec::new();
+        for (file, mut path) in roots {
+            collect(file, &mut path, 0, depth, &mut out);
+        }
+        out.sort_by_key(|dir| Reverse(dir.usage.size));
+        Ok(out)
+    }
+}
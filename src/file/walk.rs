@@ -0,0 +1,213 @@
+use std::{
+    fs::{self, Metadata},
+    io,
+    path::Path,
+    sync::atomic::{AtomicU64, Ordering},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use tracing::debug;
+
+use crate::{
+    progress::{Phase, Progress, ProgressFn, STEP},
+    rules::{Filter, Ignores},
+};
+
+use super::{sort_files, File, FileKind};
+
+pub(super) fn unix_timestamp(time: io::Result<SystemTime>) -> Option<i64> {
+    let time = time.ok()?;
+    Some(match time.duration_since(UNIX_EPOCH) {
+        Ok(after) => after.as_secs() as i64,
+        Err(before) => -(before.duration().as_secs() as i64),
+    })
+}
+
+fn file_from_metadata(name: String, metadata: &Metadata) -> File {
+    File {
+        kind: if metadata.is_dir() {
+            FileKind::Directory
+        } else {
+            FileKind::File
+        },
+        size: if metadata.is_file() {
+            metadata.len()
+        } else {
+            0
+        },
+        created: unix_timestamp(metadata.created()),
+        modified: unix_timestamp(metadata.modified()),
+        accessed: unix_timestamp(metadata.accessed()),
+        attributes: file_attributes(&name, metadata),
+        id: file_id(metadata),
+        children: vec![],
+        name,
+    }
+}
+
+#[cfg(windows)]
+fn file_attributes(_name: &str, metadata: &Metadata) -> u32 {
+    use std::os::windows::fs::MetadataExt;
+    metadata.file_attributes() & !super::attributes::CAVER
+}
+
+#[cfg(not(windows))]
+fn file_attributes(name: &str, metadata: &Metadata) -> u32 {
+    let mut attributes = 0;
+    if name.starts_with('.') {
+        attributes |= super::attributes::HIDDEN;
+    }
+    if metadata.permissions().readonly() {
+        attributes |= super::attributes::READONLY;
+    }
+    attributes
+}
+
+#[cfg(unix)]
+fn file_id(metadata: &Metadata) -> u64 {
+    use std::os::unix::fs::MetadataExt;
+    metadata.ino()
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &Metadata) -> u64 {
+    0
+}
+
+#[cfg(unix)]
+fn device(metadata: &Metadata) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    Some(metadata.dev())
+}
+
+#[cfg(not(unix))]
+fn device(_metadata: &Metadata) -> Option<u64> {
+    None
+}
+
+/// Counts the walked files to report them every [`STEP`]
+struct Counter<'a> {
+    source: &'a str,
+    walked: AtomicU64,
+    progress: ProgressFn<'a>,
+}
+
+impl Counter<'_> {
+    fn add(&self, count: u64) {
+        let before = self.walked.fetch_add(count, Ordering::Relaxed);
+        if before / STEP != (before + count) / STEP {
+            (self.progress)(Progress {
+                source: self.source,
+                phase: Phase::Walking,
+                done: before + count,
+                total: None,
+            });
+        }
+    }
+}
+
+/// `ignored` tells if the directory at `path` is itself ignored
+fn walk_dir(
+    path: &Path,
+    root_device: Option<u64>,
+    counter: &Counter,
+    filter: &Filter,
+    ignores: &Ignores,
+    ignored: bool,
+) -> Vec<File> {
+    let entries = match fs::read_dir(path) {
+        Ok(entries) => entries.filter_map(Result::ok).collect::<Vec<_>>(),
+        Err(e) => {
+            debug!(path = %path.display(), "skipped : {e}");
+            return vec![];
+        }
+    };
+
+    // Everything under an ignored directory is ignored, its ignore files don't matter
+    let ignores = match ignored {
+        true => ignores.clone(),
+        false => ignores.enter(path, |name| {
+            entries.iter().any(|entry| entry.file_name() == name)
+        }),
+    };
+
+    let mut files: Vec<File> = entries
+        .into_par_iter()
+        .filter_map(|entry| {
+            let path = entry.path();
+            // Names are kept as strings, a lossy one would give a path that doesn't exist
+            let Some(name) = entry.file_name().to_str().map(String::from) else {
+                debug!(path = %path.display(), "skipped : the name isn't valid unicode");
+                return None;
+            };
+
+            // Symlinks are not followed, they are indexed as plain files
+            let metadata = entry.metadata().ok()?;
+            let mut file = file_from_metadata(name, &metadata);
+
+            if !filter.keeps(&path, &file)
+                || !filter.mark_ignored(&path, &mut file, &ignores, ignored)
+            {
+                return None;
+            }
+
+            // Other mount points are indexed on their own
+            if metadata.is_dir() && device(&metadata) == root_device {
+                file.children = walk_dir(
+                    &path,
+                    root_device,
+                    counter,
+                    filter,
+                    &ignores,
+                    file.is_ignored(),
+                );
+            }
+
+            counter.add(1);
+            Some(file)
+        })
+        .collect();
+    sort_files(&mut files);
+    files
+}
+
+/// Builds the file tree under `root` by walking the directories,
+/// used where the MFT can't be read. Files whose name isn't valid unicode are left out
+pub fn walk(root: &Path, filter: &Filter, progress: ProgressFn) -> io::Result<File> {
+    let metadata = fs::metadata(root)?;
+    let name = root.to_str().ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "the path isn't valid unicode")
+    })?;
+    let mut file = file_from_metadata(name.to_string(), &metadata);
+
+    let counter = Counter {
+        source: &file.name,
+        walked: AtomicU64::new(0),
+        progress,
+    };
+    progress(Progress {
+        source: counter.source,
+        phase: Phase::Walking,
+        done: 0,
+        total: None,
+    });
+
+    let children = if metadata.is_dir() {
+        let ignores = filter.ignores(root);
+        walk_dir(root, device(&metadata), &counter, filter, &ignores, false)
+    } else {
+        vec![]
+    };
+
+    let walked = counter.walked.into_inner();
+    progress(Progress {
+        source: &file.name,
+        phase: Phase::Done,
+        done: walked,
+        total: Some(walked),
+    });
+
+    file.children = children;
+    Ok(file)
+}
@@ -0,0 +1,419 @@
+#[cfg(test)]
+mod test;
+
+use std::{
+    collections::{BTreeMap, HashMap},
+    fmt::{self, Display},
+    path::PathBuf,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    error::{CaverError, CaverResult},
+};
+
+/// Everything a key can be bound to, shared by every frontend
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Open,
+    RevealInFolder,
+    CopyPath,
+    CopyName,
+    NextResult,
+    PreviousResult,
+    NextPage,
+    PreviousPage,
+    FirstResult,
+    LastResult,
+    RefineQuery,
+    ToggleFieldFilter,
+    ClearQuery,
+    DiskUsage,
+    RebuildIndex,
+    Quit,
+}
+
+impl Action {
+    pub const ALL: [Action; 16] = [
+        Self::Open,
+        Self::RevealInFolder,
+        Self::CopyPath,
+        Self::CopyName,
+        Self::NextResult,
+        Self::PreviousResult,
+        Self::NextPage,
+        Self::PreviousPage,
+        Self::FirstResult,
+        Self::LastResult,
+        Self::RefineQuery,
+        Self::ToggleFieldFilter,
+        Self::ClearQuery,
+        Self::DiskUsage,
+        Self::RebuildIndex,
+        Self::Quit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Open => "open",
+            Self::RevealInFolder => "reveal_in_folder",
+            Self::CopyPath => "copy_path",
+            Self::CopyName => "copy_name",
+            Self::NextResult => "next_result",
+            Self::PreviousResult => "previous_result",
+            Self::NextPage => "next_page",
+            Self::PreviousPage => "previous_page",
+            Self::FirstResult => "first_result",
+            Self::LastResult => "last_result",
+            Self::RefineQuery => "refine_query",
+            Self::ToggleFieldFilter => "toggle_field_filter",
+            Self::ClearQuery => "clear_query",
+            Self::DiskUsage => "disk_usage",
+            Self::RebuildIndex => "rebuild_index",
+            Self::Quit => "quit",
+        }
+    }
+
+    fn default_bindings(&self) -> &'static [&'static str] {
+        match self {
+            Self::Open => &["enter"],
+            Self::RevealInFolder => &["ctrl+e"],
+            Self::CopyPath => &["ctrl+y"],
+            Self::CopyName => &["alt+y"],
+            Self::NextResult => &["down"],
+            Self::PreviousResult => &["up"],
+            Self::NextPage => &["pagedown"],
+            Self::PreviousPage => &["pageup"],
+            Self::FirstResult => &["ctrl+home"],
+            Self::LastResult => &["ctrl+end"],
+            Self::RefineQuery => &["tab"],
+            Self::ToggleFieldFilter => &["ctrl+f"],
+            Self::ClearQuery => &["ctrl+u"],
+            Self::DiskUsage => &["ctrl+d"],
+            Self::RebuildIndex => &["ctrl+r ctrl+r"],
+            Self::Quit => &["esc", "ctrl+c"],
+        }
+    }
+}
+
+impl Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(self.name())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum KeyCode {
+    Char(char),
+    F(u8),
+    Enter,
+    Esc,
+    Tab,
+    BackTab,
+    Backspace,
+    Delete,
+    Insert,
+    Up,
+    Down,
+    Left,
+    Right,
+    Home,
+    End,
+    PageUp,
+    PageDown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct Modifiers {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Key {
+    pub code: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl Key {
+    /// Shift is folded into characters so `shift+a` and `A` are the same key
+    pub fn new(code: KeyCode, mut modifiers: Modifiers) -> Self {
+        let code = match code {
+            KeyCode::Char(c) if modifiers.shift => {
+                modifiers.shift = false;
+                KeyCode::Char(c.to_ascii_uppercase())
+            }
+            code => code,
+        };
+
+        Self { code, modifiers }
+    }
+
+    pub fn parse(s: &str) -> CaverResult<Self> {
+        let invalid = || CaverError::InvalidKey(s.to_string());
+
+        let mut modifiers = Modifiers::default();
+        let mut parts = s.split('+').collect::<Vec<_>>();
+        // `+` and `ctrl++` bind the plus key
+        if s == "+" || s.ends_with("++") {
+            parts.truncate(parts.len() - 2);
+            parts.push("+");
+        }
+        let code = parts.pop().ok_or_else(invalid)?;
+
+        for modifier in parts {
+            match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => modifiers.ctrl = true,
+                "alt" => modifiers.alt = true,
+                "shift" => modifiers.shift = true,
+                _ => return Err(invalid()),
+            }
+        }
+
+        let mut chars = code.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(c), None) => KeyCode::Char(c),
+            _ => match code.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                "pageup" => KeyCode::PageUp,
+                "pagedown" => KeyCode::PageDown,
+                f => match f.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n @ 1..=24) => KeyCode::F(n),
+                    _ => return Err(invalid()),
+                },
+            },
+        };
+
+        Ok(Self::new(code, modifiers))
+    }
+}
+
+impl Display for Key {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.modifiers.ctrl {
+            f.write_str("ctrl+")?;
+        }
+        if self.modifiers.alt {
+            f.write_str("alt+")?;
+        }
+        if self.modifiers.shift {
+            f.write_str("shift+")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("space"),
+            KeyCode::Char(c) => write!(f, "{c}"),
+            KeyCode::F(n) => write!(f, "f{n}"),
+            KeyCode::Enter => f.write_str("enter"),
+            KeyCode::Esc => f.write_str("esc"),
+            KeyCode::Tab => f.write_str("tab"),
+            KeyCode::BackTab => f.write_str("backtab"),
+            KeyCode::Backspace => f.write_str("backspace"),
+            KeyCode::Delete => f.write_str("delete"),
+            KeyCode::Insert => f.write_str("insert"),
+            KeyCode::Up => f.write_str("up"),
+            KeyCode::Down => f.write_str("down"),
+            KeyCode::Left => f.write_str("left"),
+            KeyCode::Right => f.write_str("right"),
+            KeyCode::Home => f.write_str("home"),
+            KeyCode::End => f.write_str("end"),
+            KeyCode::PageUp => f.write_str("pageup"),
+            KeyCode::PageDown => f.write_str("pagedown"),
+        }
+    }
+}
+
+/// A sequence of keys pressed one after the other, written `ctrl+k ctrl+c`
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord(pub Vec<Key>);
+
+impl Chord {
+    pub fn parse(s: &str) -> CaverResult<Self> {
+        let keys = s
+            .split_whitespace()
+            .map(Key::parse)
+            .collect::<CaverResult<Vec<_>>>()?;
+
+        if keys.is_empty() {
+            return Err(CaverError::InvalidKey(s.to_string()));
+        }
+
+        Ok(Self(keys))
+    }
+}
+
+impl Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, key) in self.0.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" ")?;
+            }
+            write!(f, "{key}")?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum KeymapFileBinding {
+    One(String),
+    Many(Vec<String>),
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct KeymapFile {
+    #[serde(default)]
+    bindings: BTreeMap<Action, KeymapFileBinding>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<Chord>>,
+    actions: HashMap<Chord, Action>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::new(
+            Action::ALL
+                .iter()
+                .map(|action| {
+                    let chords = action
+                        .default_bindings()
+                        .iter()
+                        .map(|s| Chord::parse(s).unwrap())
+                        .collect();
+                    (*action, chords)
+                })
+                .collect(),
+        )
+        .unwrap()
+    }
+}
+
+impl Keymap {
+    /// Fails if a chord is bound twice, or if it is the start of another chord
+    /// since the longer one could never be typed
+    pub fn new(bindings: BTreeMap<Action, Vec<Chord>>) -> CaverResult<Self> {
+        let mut actions = HashMap::new();
+
+        for (&action, chords) in &bindings {
+            for chord in chords {
+                if let Some(&other) = actions.get(chord) {
+                    return Err(CaverError::KeymapConflict(chord.to_string(), other, action));
+                }
+                actions.insert(chord.clone(), action);
+            }
+        }
+
+        for (chord, &action) in &actions {
+            for (other_chord, &other) in &actions {
+                if chord.0.len() < other_chord.0.len() && other_chord.0.starts_with(&chord.0) {
+                    return Err(CaverError::KeymapConflict(
+                        format!("{chord} / {other_chord}"),
+                        action,
+                        other,
+                    ));
+                }
+            }
+        }
+
+        Ok(Self { bindings, actions })
+    }
+
+    /// Default bindings overridden by the actions present in the toml
+    pub fn from_toml(s: &str) -> CaverResult<Self> {
+        let file: KeymapFile = toml::from_str(s).map_err(CaverError::InvalidKeymap)?;
+
+        let mut bindings = Self::default().bindings;
+        for (action, binding) in file.bindings {
+            let chords = match binding {
+                KeymapFileBinding::One(s) => vec![Chord::parse(&s)?],
+                KeymapFileBinding::Many(v) => v
+                    .iter()
+                    .map(|s| Chord::parse(s))
+                    .collect::<CaverResult<_>>()?,
+            };
+            bindings.insert(action, chords);
+        }
+
+        Self::new(bindings)
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        config::dir().map(|dir| dir.join("keymap.toml"))
+    }
+
+    /// Loads the user's keymap file, or the defaults if there is none
+    pub fn load() -> CaverResult<Self> {
+        config::load(Self::path(), Self::from_toml)
+    }
+
+    pub fn bindings(&self) -> &BTreeMap<Action, Vec<Chord>> {
+        &self.bindings
+    }
+
+    pub fn action(&self, chord: &Chord) -> Option<Action> {
+        self.actions.get(chord).copied()
+    }
+
+    fn is_prefix(&self, keys: &[Key]) -> bool {
+        self.actions
+            .keys()
+            .any(|chord| chord.0.len() > keys.len() && chord.0.starts_with(keys))
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum KeyMatch {
+    Action(Action),
+    /// The keys so far are the start of a chord
+    Pending,
+    /// The keys don't lead to any binding
+    Unbound(Vec<Key>),
+}
+
+/// Turns key presses into actions, keeping track of partially typed chords
+#[derive(Debug, Default)]
+pub struct ChordMatcher {
+    pending: Vec<Key>,
+}
+
+impl ChordMatcher {
+    pub fn pending(&self) -> &[Key] {
+        &self.pending
+    }
+
+    pub fn feed(&mut self, keymap: &Keymap, key: Key) -> KeyMatch {
+        self.pending.push(key);
+
+        let chord = Chord(std::mem::take(&mut self.pending));
+        if let Some(action) = keymap.action(&chord) {
+            KeyMatch::Action(action)
+        } else if keymap.is_prefix(&chord.0) {
+            self.pending = chord.0;
+            KeyMatch::Pending
+        } else {
+            KeyMatch::Unbound(chord.0)
+        }
+    }
+}
@@ -0,0 +1,95 @@
+use std::collections::BTreeMap;
+
+use crate::error::CaverError;
+
+use super::{Action, Chord, ChordMatcher, Key, KeyCode, KeyMatch, Keymap, Modifiers};
+
+#[test]
+fn parse_keys() {
+    assert_eq!(
+        Key::parse("ctrl+alt+f5").unwrap(),
+        Key {
+            code: KeyCode::F(5),
+            modifiers: Modifiers {
+                ctrl: true,
+                alt: true,
+                shift: false,
+            },
+        }
+    );
+    assert_eq!(Key::parse("shift+a").unwrap(), Key::parse("A").unwrap());
+    assert_eq!(Key::parse("ctrl++").unwrap().code, KeyCode::Char('+'));
+    assert_eq!(Key::parse("space").unwrap().code, KeyCode::Char(' '));
+    assert!(Key::parse("hyper+a").is_err());
+    assert!(Key::parse("f25").is_err());
+
+    let chord = Chord::parse("ctrl+k  ctrl+PageDown").unwrap();
+    assert_eq!(chord.to_string(), "ctrl+k ctrl+pagedown");
+}
+
+#[test]
+fn user_keymap_overrides_defaults() {
+    let keymap = Keymap::from_toml(
+        r#"
+        [bindings]
+        open = ["ctrl+o", "g o"]
+        copy_path = "ctrl+k ctrl+p"
+        quit = []
+        "#,
+    )
+    .unwrap();
+
+    let bindings = keymap.bindings();
+    assert_eq!(bindings[&Action::Open].len(), 2);
+    assert!(bindings[&Action::Quit].is_empty());
+    assert_eq!(
+        keymap.action(&Chord::parse("ctrl+e").unwrap()),
+        Some(Action::RevealInFolder)
+    );
+    assert_eq!(keymap.action(&Chord::parse("esc").unwrap()), None);
+
+    let mut matcher = ChordMatcher::default();
+    let key = |s| Key::parse(s).unwrap();
+    assert_eq!(matcher.feed(&keymap, key("ctrl+k")), KeyMatch::Pending);
+    assert_eq!(
+        matcher.feed(&keymap, key("ctrl+p")),
+        KeyMatch::Action(Action::CopyPath)
+    );
+    assert_eq!(matcher.feed(&keymap, key("g")), KeyMatch::Pending);
+    assert_eq!(
+        matcher.feed(&keymap, key("x")),
+        KeyMatch::Unbound(vec![key("g"), key("x")])
+    );
+    assert!(matcher.pending().is_empty());
+}
+
+#[test]
+fn keymap_conflicts() {
+    let same_chord = Keymap::from_toml(
+        r#"
+        [bindings]
+        open = "ctrl+y"
+        "#,
+    );
+    assert!(matches!(
+        same_chord,
+        Err(CaverError::KeymapConflict(
+            _,
+            Action::Open,
+            Action::CopyPath
+        ))
+    ));
+
+    let mut bindings = BTreeMap::new();
+    bindings.insert(Action::Open, vec![Chord::parse("g").unwrap()]);
+    bindings.insert(Action::Quit, vec![Chord::parse("g q").unwrap()]);
+    assert!(matches!(
+        Keymap::new(bindings),
+        Err(CaverError::KeymapConflict(..))
+    ));
+
+    assert!(matches!(
+        Keymap::from_toml("[bindings]\nfly = \"f\""),
+        Err(CaverError::InvalidKeymap(_))
+    ));
+}
@@ -0,0 +1,31 @@
+//! Everything-like file search : indexes every disk once, then answers queries from memory.
+//!
+//! ```no_run
+//! use caver::{FileIndex, SearchParams};
+//!
+//! let index = FileIndex::create()?;
+//! for (file, path) in index.search(SearchParams::from_str("main.rs path<src>")) {
+//!     println!("{} ({} bytes)", path.display(), file.size);
+//! }
+//! # Ok::<(), caver::CaverError>(())
+//! ```
+
+pub mod config;
+#[cfg(feature = "daemon")]
+pub mod daemon;
+mod db;
+pub mod disk;
+pub mod error;
+pub mod file;
+pub mod keymap;
+pub mod output;
+pub mod progress;
+pub mod rules;
+pub mod search;
+#[cfg(test)]
+mod test_util;
+
+pub use disk::DiskLetter;
+pub use error::{CaverError, CaverResult};
+pub use file::{index::FileIndex, File, FileKind};
+pub use search::SearchParams;
@@ -1,33 +1,688 @@
-#![feature(extract_if)]
-#![feature(iter_advance_by)]
+mod tui;
 
-pub mod disk;
-pub mod error;
-pub mod file;
-pub mod search;
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{self, IsTerminal, Write},
+    path::{Path, PathBuf},
+    process::ExitCode,
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 
-use std::{env, time::Instant};
+use caver::{
+    daemon::{self, Client, Daemon},
+    error::{IntoCaverResult, WithContext},
+    file::kinds::{ExtensionCount, Kinds},
+    keymap::Keymap,
+    output::{self, OutputFormat, ResultRecord},
+    progress::{Phase, Progress, ProgressFn},
+    rules::Rules,
+    search::{ContentIndex, ContentRules},
+    CaverError, CaverResult, FileIndex, SearchParams,
+};
+use clap::{ArgAction, Args, Parser, Subcommand};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use rayon::iter::{IntoParallelRefMutIterator, ParallelIterator};
+use tracing::{info, warn};
+use tracing_subscriber::{fmt, layer::SubscriberExt, util::SubscriberInitExt, EnvFilter};
+use tui::Exit;
 
-use file::index::FileIndex;
+#[derive(Parser)]
+#[command(
+    version,
+    about,
+    override_usage = "caver [OPTIONS] [QUERY]...\n       caver [OPTIONS] <COMMAND>"
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
 
-fn main() {
-    let args = env::args().collect::<Vec<_>>();
+    #[command(flatten)]
+    search: SearchArgs,
 
-    if args.get(1).is_some_and(|s| s == "reset") || !FileIndex::SAVE_PATH.exists() {
-        let fi = FileIndex::create().unwrap();
-        fi.save().unwrap();
-    } else {
-        let fi_fetch_start = Instant::now();
-        let fi = FileIndex::fetch_from_db().unwrap();
-        println!("fi fetch time : {:?}", Instant::now() - fi_fetch_start);
+    /// Log more, up to -vvv. `CAVER_LOG` takes precedence, e.g. `CAVER_LOG=caver=debug`.
+    /// While the terminal ui runs, logs go to `caver.log` in the temp directory
+    #[arg(short, long, global = true, action = ArgAction::Count)]
+    verbose: u8,
 
-        let search_start = Instant::now();
-        let results = fi.search_str("path<minecraft assets>");
+    /// Only log errors
+    #[arg(short, long, global = true, conflicts_with = "verbose")]
+    quiet: bool,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Rebuild the index from scratch
+    Reset,
+    /// Search interactively
+    Tui {
+        /// Walk these directories instead of using the saved index
+        #[arg(long)]
+        root: Vec<PathBuf>,
+    },
+    /// Print the effective keyboard shortcuts
+    Keys,
+    /// Print how each disk was indexed and what it holds
+    Stats,
+    /// Print the largest directories
+    Du {
+        /// Only look under this directory
+        path: Option<PathBuf>,
+
+        /// How many levels of directories to list under it
+        #[arg(short, long, default_value_t = 1)]
+        depth: usize,
+
+        /// How many directories to print
+        #[arg(short, long, default_value_t = 20)]
+        top: usize,
+    },
+    /// Find files with the same content, e.g. `caver dupes path<D:\Photos>`
+    Dupes {
+        /// Only compare the files matching this query
+        query: Vec<String>,
+    },
+    /// Print how a query is run, without running it
+    Explain { query: Vec<String> },
+    /// Read the new and changed files under the paths of `content.toml` into the content index
+    IndexContent,
+    /// Print what changed between two saved indexes
+    Diff {
+        /// A copy of an older index
+        old: PathBuf,
+
+        /// The index to compare it to, the current one by default
+        new: Option<PathBuf>,
+
+        /// Only compare the files matching this query, e.g. `path<C:\Program Files>`
+        #[arg(long, default_value = "")]
+        query: String,
+
+        /// plain, json, jsonl, csv, tsv or null
+        #[arg(short, long, default_value = "plain")]
+        format: OutputFormat,
+    },
+    /// Keep the index in memory and answer searches from other caver processes
+    Serve {
+        /// Name of the local socket (named pipe on windows)
+        #[arg(long, default_value_t = daemon::default_socket())]
+        socket: String,
+
+        /// Seconds between two rebuilds of the index
+        #[arg(long, default_value_t = 600)]
+        refresh: u64,
+
+        /// Also answer searches over HTTP on this address, e.g. `127.0.0.1:8080`
+        #[arg(long)]
+        http: Option<String>,
+
+        /// Let `--http` listen on an address other machines can reach, without authentication
+        #[arg(long, requires = "http")]
+        http_remote: bool,
+    },
+}
+
+#[derive(Args)]
+struct SearchArgs {
+    /// Search query, e.g. `main.rs path<src>`
+    query: Vec<String>,
+
+    /// How results are printed : plain, json, jsonl, csv, tsv or null
+    #[arg(short, long, default_value = "plain")]
+    format: OutputFormat,
+
+    /// Print the lines matching `content<...>` with this many lines around them, like `grep -C`
+    #[arg(short = 'C', long)]
+    context: Option<usize>,
+
+    /// Files larger than this many bytes are not searched by `content<...>`, 0 for no limit.
+    /// The search doesn't go through the daemon when it is set
+    #[arg(long)]
+    max_content_size: Option<u64>,
+
+    /// Load the index even if a daemon is running
+    #[arg(long)]
+    no_daemon: bool,
+
+    /// Local socket of the daemon to ask first
+    #[arg(long, default_value_t = daemon::default_socket())]
+    socket: String,
+}
+
+/// Exit codes, 2 is used by clap for invalid arguments
+mod exit {
+    pub const FAILURE: u8 = 1;
+    pub const PERMISSION_DENIED: u8 = 3;
+    pub const INVALID_CONFIG: u8 = 4;
+    pub const DAEMON: u8 = 5;
+}
+
+fn exit_code(e: &CaverError) -> u8 {
+    match e.root() {
+        CaverError::ElevationError => exit::PERMISSION_DENIED,
+        CaverError::IOError(e) if e.kind() == io::ErrorKind::PermissionDenied => {
+            exit::PERMISSION_DENIED
+        }
+        CaverError::InvalidKey(_)
+        | CaverError::InvalidKeymap(_)
+        | CaverError::KeymapConflict(..)
+        | CaverError::InvalidRules(_)
+        | CaverError::InvalidContentRules(_)
+        | CaverError::InvalidKinds(_)
+        | CaverError::InvalidPattern(_) => exit::INVALID_CONFIG,
+        CaverError::InvalidMessage(_)
+        | CaverError::MessageTooLarge(_)
+        | CaverError::Daemon(_)
+        | CaverError::Http(_) => exit::DAEMON,
+        _ => exit::FAILURE,
+    }
+}
 
-        println!("search time : {:?}", Instant::now() - search_start);
-        println!("results : {:?}", results.len());
-        results
-            .iter()
-            .for_each(|(_, file)| println!("{}", file.to_str().unwrap()));
+fn init_logging(cli: &Cli) {
+    let level = match (cli.quiet, cli.verbose) {
+        (true, _) => "error",
+        (_, 0) => "warn",
+        (_, 1) => "info",
+        (_, 2) => "debug",
+        _ => "trace",
+    };
+    let filter = EnvFilter::try_from_env("CAVER_LOG").unwrap_or_else(|_| EnvFilter::new(level));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(|| LogTarget::Stderr))
+        .with(fmt::layer().with_ansi(false).with_writer(|| LogTarget::Tui))
+        .init();
+}
+
+/// Receives the logs while the terminal ui owns the screen, see [`log_to_file`]
+static TUI_LOG: Mutex<Option<fs::File>> = Mutex::new(None);
+
+/// Where a log layer writes, what isn't meant for the current target is dropped
+enum LogTarget {
+    Stderr,
+    Tui,
+}
+
+impl Write for LogTarget {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match (self, TUI_LOG.lock().unwrap().as_mut()) {
+            (Self::Stderr, None) => io::stderr().write(buf),
+            (Self::Tui, Some(file)) => file.write(buf),
+            _ => Ok(buf.len()),
+        }
     }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match (self, TUI_LOG.lock().unwrap().as_mut()) {
+            (Self::Stderr, None) => io::stderr().flush(),
+            (Self::Tui, Some(file)) => file.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+fn tui_log_path() -> PathBuf {
+    env::temp_dir().join("caver.log")
+}
+
+/// Sends the logs to the end of the file at `path` instead of stderr,
+/// until [`log_to_stderr`]
+fn log_to_file(path: &Path) -> CaverResult<()> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .into_caver_result()
+        .context(path.to_path_buf())?;
+    *TUI_LOG.lock().unwrap() = Some(file);
+    Ok(())
+}
+
+fn log_to_stderr() {
+    TUI_LOG.lock().unwrap().take();
+}
+
+/// Draws a progress bar per disk on stderr while `f` runs
+fn with_progress_bars<T>(f: impl FnOnce(ProgressFn) -> T) -> T {
+    let bars = MultiProgress::new();
+    let by_source = Mutex::new(HashMap::<String, ProgressBar>::new());
+    let counted =
+        ProgressStyle::with_template("{prefix:20} {msg:16} {wide_bar} {pos}/{len}").unwrap();
+    let walked =
+        ProgressStyle::with_template("{prefix:20} {msg:16} {spinner} {pos} files").unwrap();
+
+    let res = f(&|progress: Progress| {
+        let mut by_source = by_source.lock().unwrap();
+        let bar = by_source
+            .entry(progress.source.to_string())
+            .or_insert_with(|| {
+                let bar = bars.add(ProgressBar::no_length());
+                bar.set_prefix(progress.source.to_string());
+                bar.enable_steady_tick(Duration::from_millis(100));
+                bar
+            });
+
+        match progress.total {
+            Some(total) => {
+                bar.set_style(counted.clone());
+                bar.set_length(total);
+            }
+            None => bar.set_style(walked.clone()),
+        }
+        bar.set_message(progress.phase.to_string());
+        bar.set_position(progress.done);
+
+        if progress.phase == Phase::Done {
+            bar.finish_and_clear();
+        }
+    });
+
+    let _ = bars.clear();
+    res
+}
+
+fn rebuild_index_with_progress(progress: ProgressFn) -> CaverResult<FileIndex> {
+    let mut fi = FileIndex::create_with(&Rules::load()?, progress)?;
+    fi.save()?;
+    fi.kinds = Kinds::load()?;
+    update_content_index(&mut fi)?;
+    Ok(fi)
+}
+
+/// The saved content index, if there is a usable one
+fn load_content_index() -> Option<ContentIndex> {
+    if !Path::new(ContentIndex::SAVE_PATH).exists() {
+        return None;
+    }
+
+    ContentIndex::fetch_from_db()
+        .inspect_err(|e| warn!("{e}, content searches read the files"))
+        .ok()
+}
+
+/// Brings the content index up to date with `fi`, when `content.toml` lists paths or one was built
+fn update_content_index(fi: &mut FileIndex) -> CaverResult<()> {
+    let rules = ContentRules::load()?;
+    let mut content = (fi.content.take())
+        .or_else(load_content_index)
+        .unwrap_or_default();
+    if rules.paths.is_empty() && content.is_empty() {
+        return Ok(());
+    }
+
+    let update_start = Instant::now();
+    let update = content.update(fi, &rules);
+    info!(
+        elapsed = ?update_start.elapsed(),
+        read = update.read,
+        removed = update.removed,
+        "content index updated"
+    );
+    content.save()?;
+    fi.content = Some(content);
+    Ok(())
+}
+
+fn rebuild_index() -> CaverResult<FileIndex> {
+    let fi = with_progress_bars(rebuild_index_with_progress)?;
+    for source in fi.problems() {
+        warn!("{source}");
+    }
+    Ok(fi)
+}
+
+fn load_index() -> CaverResult<FileIndex> {
+    if !Path::new(FileIndex::SAVE_PATH).exists() {
+        return rebuild_index();
+    }
+
+    let fi_fetch_start = Instant::now();
+    let mut fi = match FileIndex::fetch_from_db() {
+        Err(e)
+            if matches!(
+                e.root(),
+                CaverError::OutdatedIndex(_) | CaverError::DeserializeError(_)
+            ) =>
+        {
+            warn!("{e}, rebuilding it");
+            rebuild_index()
+        }
+        res => res,
+    }?;
+    info!(elapsed = ?fi_fetch_start.elapsed(), "index loaded");
+
+    let rules = Rules::load()?;
+    if fi.outdated(&rules).next().is_some() {
+        info!("the rules changed, indexing the affected disks again");
+        with_progress_bars(|progress| fi.refresh(&rules, progress))?;
+        fi.save()?;
+    }
+
+    if fi.content.is_none() {
+        fi.content = load_content_index();
+    }
+    fi.kinds = Kinds::load()?;
+    Ok(fi)
+}
+
+fn main() -> ExitCode {
+    let cli = Cli::parse();
+    init_logging(&cli);
+
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        // The output was closed early, e.g. piped into `head`
+        Err(CaverError::IOError(e)) if e.kind() == io::ErrorKind::BrokenPipe => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error : {e}");
+
+            let mut source = e.source();
+            while let Some(e) = source {
+                eprintln!("  caused by : {e}");
+                source = e.source();
+            }
+
+            ExitCode::from(exit_code(&e))
+        }
+    }
+}
+
+fn run(cli: Cli) -> CaverResult<()> {
+    match cli.command {
+        Some(Command::Reset) => {
+            rebuild_index()?;
+        }
+        Some(Command::Tui { root }) => {
+            let keymap = Keymap::load()?;
+            let rules = Rules::load()?;
+            let kinds = Kinds::load()?;
+            let mut fi = if root.is_empty() {
+                load_index()?
+            } else {
+                let mut fi =
+                    with_progress_bars(|progress| FileIndex::walk_with(&root, &rules, progress))?;
+                fi.kinds = kinds.clone();
+                fi
+            };
+            let mut query = String::new();
+
+            loop {
+                // The screen is the terminal ui's until it is restored
+                log_to_file(&tui_log_path())?;
+                let mut terminal = ratatui::init();
+                let res = if root.is_empty() {
+                    tui::run(
+                        &mut terminal,
+                        &fi,
+                        keymap.clone(),
+                        query,
+                        rebuild_index_with_progress,
+                    )
+                } else {
+                    let (root, rules, kinds) = (root.clone(), rules.clone(), kinds.clone());
+                    tui::run(&mut terminal, &fi, keymap.clone(), query, move |progress| {
+                        let mut fi = FileIndex::walk_with(&root, &rules, progress)?;
+                        fi.kinds = kinds.clone();
+                        Ok(fi)
+                    })
+                };
+                ratatui::restore();
+                log_to_stderr();
+
+                match res? {
+                    Exit::Quit => break,
+                    Exit::Rebuilt { index, query: q } => (fi, query) = (*index, q),
+                }
+            }
+        }
+        Some(Command::Keys) => {
+            match Keymap::path() {
+                Some(path) if path.exists() => println!("keymap : {}", path.display()),
+                Some(path) => println!("keymap : {} (not found, defaults)", path.display()),
+                None => println!("keymap : defaults"),
+            }
+
+            let keymap = Keymap::load()?;
+            for (action, chords) in keymap.bindings() {
+                let chords = chords.iter().map(|chord| chord.to_string());
+                println!("{:<20} {}", action, chords.collect::<Vec<_>>().join(", "));
+            }
+        }
+        Some(Command::Stats) => {
+            let fi = load_index()?;
+            let stats = fi.stats();
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs() as i64;
+            let ago = |time: Option<i64>| match time {
+                Some(time) => {
+                    let age = Duration::from_secs(now.saturating_sub(time).max(0) as u64);
+                    format!("{} ago", output::human_duration(age))
+                }
+                None => "never".to_string(),
+            };
+            let extensions =
+                |counts: &[ExtensionCount], value: &dyn Fn(&ExtensionCount) -> String| {
+                    let counts = counts
+                        .iter()
+                        .map(|count| format!("{} {}", count.name, value(count)));
+                    counts.collect::<Vec<_>>().join(", ")
+                };
+
+            let indexed = stats.iter().filter_map(|stats| stats.indexed);
+            println!(
+                "index : {}, built {}, updated {}",
+                output::human_size(fi.index_size()),
+                ago(indexed.clone().min()),
+                ago(indexed.max()),
+            );
+            for stats in stats {
+                println!();
+                println!(
+                    "{:<20} {:>10} files {:>8} dirs {:>10}  {}",
+                    stats.name,
+                    stats.files,
+                    stats.directories,
+                    output::human_size(stats.size),
+                    stats.status,
+                );
+                println!(
+                    "  indexed     {} in {}, {} in the index",
+                    ago(stats.indexed),
+                    output::human_duration(Duration::from_millis(stats.index_ms)),
+                    output::human_size(stats.index_size),
+                );
+                println!(
+                    "  extensions  {}",
+                    extensions(&stats.top_extensions, &|count| count.files.to_string())
+                );
+                println!(
+                    "  largest     {}",
+                    extensions(&stats.largest_extensions, &|count| {
+                        output::human_size(count.size)
+                    })
+                );
+                if let Some(deepest) = &stats.deepest {
+                    println!("  deepest     {}", deepest.display());
+                }
+                println!("  longest     {}", stats.longest_names.join(", "));
+            }
+
+            println!();
+            for kind in fi.kind_stats() {
+                println!(
+                    "{:<20} {:>10} files {:>10}",
+                    kind.name,
+                    kind.files,
+                    output::human_size(kind.size),
+                );
+            }
+        }
+        Some(Command::Du { path, depth, top }) => {
+            let path = path.map(std::path::absolute).transpose()?;
+            let fi = load_index()?;
+            for dir in fi.du(path.as_deref(), depth)?.into_iter().take(top) {
+                println!(
+                    "{:>10} {:>10} files {:>8} dirs  {}",
+                    output::human_size(dir.usage.size),
+                    dir.usage.files,
+                    dir.usage.directories,
+                    dir.path.display(),
+                );
+            }
+        }
+        Some(Command::Dupes { query }) => {
+            let fi = load_index()?;
+            let groups = fi.dupes(SearchParams::from_str(&query.join(" ")));
+
+            let mut out = io::stdout().lock();
+            for group in &groups {
+                writeln!(
+                    out,
+                    "{} wasted, {} copies of {}",
+                    output::human_size(group.wasted()),
+                    group.files.len(),
+                    output::human_size(group.size)
+                )?;
+                for (_, path) in &group.files {
+                    writeln!(out, "  {}", path.display())?;
+                }
+            }
+
+            let wasted = groups.iter().map(|group| group.wasted()).sum();
+            info!(groups = groups.len(), "found duplicates");
+            writeln!(
+                out,
+                "{} groups, {} wasted",
+                groups.len(),
+                output::human_size(wasted)
+            )?;
+        }
+        Some(Command::Explain { query }) => {
+            let content = load_content_index();
+            let params = SearchParams::from_str(&query.join(" "));
+            print!("{}", params.explain(content.as_ref()));
+        }
+        Some(Command::IndexContent) => {
+            let mut fi = load_index()?;
+            update_content_index(&mut fi)?;
+            match fi.content {
+                Some(content) => println!("{} files in the content index", content.len()),
+                None => match ContentRules::path() {
+                    Some(path) => println!("no paths to index, see {}", path.display()),
+                    None => println!("no paths to index"),
+                },
+            }
+        }
+        Some(Command::Diff {
+            old,
+            new,
+            query,
+            format,
+        }) => {
+            let old = FileIndex::fetch_from(&old)?;
+            let new = match new {
+                Some(path) => FileIndex::fetch_from(&path)?,
+                None => load_index()?,
+            };
+
+            let params = SearchParams::from_str(&query).with_kinds(Kinds::load()?);
+            let changes = old.diff(&new, &params);
+            info!(changes = changes.len(), "compared");
+            output::write_changes(io::stdout().lock(), format, &changes)?;
+        }
+        Some(Command::Serve {
+            socket,
+            refresh,
+            http,
+            http_remote,
+        }) => {
+            let daemon = Arc::new(Daemon::new(load_index()?));
+            daemon.refresh_every(Duration::from_secs(refresh), || {
+                let fi = rebuild_index_with_progress(&|_| {})?;
+                for source in fi.problems() {
+                    warn!("{source}");
+                }
+                Ok(fi)
+            });
+
+            if let Some(addr) = http {
+                let server = daemon::http::bind(&addr, http_remote)?;
+                let daemon = daemon.clone();
+                thread::spawn(move || daemon.serve_http(server));
+                eprintln!("listening on http://{addr}");
+            }
+
+            eprintln!("listening on {socket}");
+            daemon.serve(&socket)?;
+        }
+        None => {
+            let query = cli.search.query.join(" ");
+
+            // Falls back to loading the index when no daemon answers, or it fails
+            let client = (!cli.search.no_daemon && cli.search.max_content_size.is_none())
+                .then(|| Client::connect(&cli.search.socket).ok())
+                .flatten();
+
+            let params = || {
+                let params = SearchParams::from_str(&query);
+                match cli.search.max_content_size {
+                    Some(size) => params.with_max_content_size((size > 0).then_some(size)),
+                    None => params,
+                }
+            };
+
+            let records = client.and_then(|mut client| {
+                (client.search_all(&query))
+                    .inspect_err(|e| warn!("{e}, searching without the daemon"))
+                    .ok()
+            });
+            let mut records = match records {
+                Some(records) => records,
+                None => {
+                    let fi = load_index()?;
+
+                    let search_start = Instant::now();
+                    let results = fi.search(params());
+                    info!(elapsed = ?search_start.elapsed(), "searched");
+
+                    results
+                        .into_iter()
+                        .map(|(file, path)| ResultRecord::new(file, path))
+                        .collect::<Vec<_>>()
+                }
+            };
+            info!(results = records.len(), "found");
+
+            let format = cli.search.format;
+            let color = format == OutputFormat::Plain
+                && io::stdout().is_terminal()
+                && env::var_os("NO_COLOR").is_none();
+            let context = match format {
+                OutputFormat::Json | OutputFormat::Jsonl => Some(cli.search.context.unwrap_or(0)),
+                _ => cli.search.context,
+            };
+            let with_hits = match format {
+                OutputFormat::Json | OutputFormat::Jsonl => true,
+                OutputFormat::Plain => color || context.is_some(),
+                _ => false,
+            };
+            if with_hits {
+                let params = params();
+                records.par_iter_mut().for_each(|record| {
+                    record.hit = Some(params.hit(&record.name, &record.path, context));
+                });
+            }
+
+            output::write_results(io::stdout().lock(), format, &records, color)?;
+        }
+    }
+
+    Ok(())
 }
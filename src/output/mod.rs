@@ -0,0 +1,390 @@
+#[cfg(test)]
+mod test;
+
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    ops::Range,
+    path::{Path, PathBuf},
+    str::FromStr,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    file::{
+        diff::{Change, ChangeKind},
+        File, FileKind,
+    },
+    search::SearchHit,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum OutputFormat {
+    /// One path per line
+    #[default]
+    Plain,
+    /// A single json array of results
+    Json,
+    /// One json object per line
+    Jsonl,
+    Csv,
+    Tsv,
+    /// Paths terminated by a NUL byte, for `xargs -0`
+    Null,
+}
+
+impl FromStr for OutputFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "plain" => Self::Plain,
+            "json" => Self::Json,
+            "jsonl" => Self::Jsonl,
+            "csv" => Self::Csv,
+            "tsv" => Self::Tsv,
+            "null" => Self::Null,
+            _ => return Err(format!("unknown format {s}")),
+        })
+    }
+}
+
+/// Paths are serialized as plain strings when they are valid UTF-8,
+/// otherwise as `{"bytes": "<base64>"}` so no information is lost
+pub mod path_repr {
+    use std::path::{Path, PathBuf};
+
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use serde::{de, ser::SerializeMap, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(path: &Path, serializer: S) -> Result<S::Ok, S::Error> {
+        match path.to_str() {
+            Some(s) => serializer.serialize_str(s),
+            None => {
+                let mut map = serializer.serialize_map(Some(1))?;
+                map.serialize_entry(
+                    "bytes",
+                    &STANDARD.encode(path.as_os_str().as_encoded_bytes()),
+                )?;
+                map.end()
+            }
+        }
+    }
+
+    #[cfg(unix)]
+    fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+        use std::{ffi::OsString, os::unix::ffi::OsStringExt};
+        OsString::from_vec(bytes).into()
+    }
+
+    #[cfg(not(unix))]
+    fn path_from_bytes(bytes: Vec<u8>) -> PathBuf {
+        String::from_utf8_lossy(&bytes).into_owned().into()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<PathBuf, D::Error> {
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum Repr {
+            Text(String),
+            Bytes { bytes: String },
+        }
+
+        Ok(match Repr::deserialize(deserializer)? {
+            Repr::Text(s) => s.into(),
+            Repr::Bytes { bytes } => {
+                path_from_bytes(STANDARD.decode(bytes).map_err(de::Error::custom)?)
+            }
+        })
+    }
+
+    /// Same for an optional path, `None` being `null`
+    pub mod optional {
+        use std::path::PathBuf;
+
+        use serde::{Deserialize, Deserializer, Serializer};
+
+        pub fn serialize<S: Serializer>(
+            path: &Option<PathBuf>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match path {
+                Some(path) => super::serialize(path, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<PathBuf>, D::Error> {
+            #[derive(Deserialize)]
+            struct Path(#[serde(with = "super")] PathBuf);
+
+            Ok(Option::<Path>::deserialize(deserializer)?.map(|path| path.0))
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ResultRecord {
+    pub name: String,
+    #[serde(with = "path_repr")]
+    pub path: PathBuf,
+    pub kind: FileKind,
+    pub size: u64,
+    pub created: Option<i64>,
+    pub modified: Option<i64>,
+    pub accessed: Option<i64>,
+    /// Where the query matched, see [`SearchParams::hit`](crate::SearchParams::hit)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hit: Option<SearchHit>,
+}
+
+impl ResultRecord {
+    pub fn new(file: &File, path: PathBuf) -> Self {
+        Self {
+            name: file.name.clone(),
+            path,
+            kind: file.kind,
+            size: file.size,
+            created: file.created,
+            modified: file.modified,
+            accessed: file.accessed,
+            hit: None,
+        }
+    }
+}
+
+const HEADERS: [&str; 7] = [
+    "name", "path", "kind", "size", "created", "modified", "accessed",
+];
+
+fn write_delimited<W: Write>(out: W, delimiter: u8, results: &[ResultRecord]) -> io::Result<()> {
+    fn timestamp(t: Option<i64>) -> Vec<u8> {
+        t.map(|t| t.to_string().into_bytes()).unwrap_or_default()
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+
+    writer.write_record(HEADERS)?;
+    for record in results {
+        writer.write_record([
+            record.name.as_bytes(),
+            record.path.as_os_str().as_encoded_bytes(),
+            record.kind.as_str().as_bytes(),
+            record.size.to_string().as_bytes(),
+            &timestamp(record.created),
+            &timestamp(record.modified),
+            &timestamp(record.accessed),
+        ])?;
+    }
+
+    writer.flush()
+}
+
+/// `s` with `ranges` in bold red, or as is without `color`
+fn highlight(s: &str, ranges: &[Range<usize>], color: bool) -> String {
+    if !color {
+        return s.to_string();
+    }
+
+    let mut out = String::with_capacity(s.len());
+    let mut end = 0;
+    for range in ranges {
+        out.push_str(&s[end..range.start]);
+        out.push_str("\x1b[1;31m");
+        out.push_str(&s[range.clone()]);
+        out.push_str("\x1b[0m");
+        end = range.end;
+    }
+    out.push_str(&s[end..]);
+    out
+}
+
+/// The path, then its matching lines like `grep -n -C`, followed by an empty line
+fn write_plain<W: Write>(out: &mut W, record: &ResultRecord, color: bool) -> io::Result<()> {
+    let path = record.path.to_string_lossy();
+    let Some(hit) = &record.hit else {
+        return writeln!(out, "{path}");
+    };
+    writeln!(out, "{}", highlight(&path, &hit.path, color))?;
+    if hit.lines.is_empty() {
+        return Ok(());
+    }
+
+    // A line can be around several matches, or around one and match itself
+    let mut shown = BTreeMap::new();
+    for line in &hit.lines {
+        let before = (line.line - line.before.len()..).zip(&line.before);
+        for (n, text) in before.chain((line.line + 1..).zip(&line.after)) {
+            shown.entry(n).or_insert((text.as_str(), None));
+        }
+    }
+    for line in &hit.lines {
+        shown.insert(line.line, (line.text.as_str(), Some(line)));
+    }
+
+    let mut last = None;
+    for (n, (text, line)) in shown {
+        if last.is_some_and(|last| n > last + 1) {
+            writeln!(out, "--")?;
+        }
+        match line {
+            Some(line) => writeln!(
+                out,
+                "{n}:{}:{}",
+                line.column,
+                highlight(text, &line.ranges, color)
+            )?,
+            None => writeln!(out, "{n}-{text}")?,
+        }
+        last = Some(n);
+    }
+    writeln!(out)
+}
+
+/// Plain results show their [`ResultRecord::hit`] when they have one, in color if `color` is set
+pub fn write_results<W: Write>(
+    mut out: W,
+    format: OutputFormat,
+    results: &[ResultRecord],
+    color: bool,
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Plain => {
+            for record in results {
+                write_plain(&mut out, record, color)?;
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, results)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for record in results {
+                serde_json::to_writer(&mut out, record)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => write_delimited(&mut out, b',', results)?,
+        OutputFormat::Tsv => write_delimited(&mut out, b'\t', results)?,
+        OutputFormat::Null => {
+            for record in results {
+                out.write_all(record.path.as_os_str().as_encoded_bytes())?;
+                out.write_all(&[0])?;
+            }
+        }
+    }
+
+    out.flush()
+}
+
+const CHANGE_HEADERS: [&str; 5] = ["kind", "path", "from", "old_size", "new_size"];
+
+fn write_delimited_changes<W: Write>(out: W, delimiter: u8, changes: &[Change]) -> io::Result<()> {
+    fn size(size: Option<u64>) -> Vec<u8> {
+        size.map(|size| size.to_string().into_bytes())
+            .unwrap_or_default()
+    }
+
+    let mut writer = csv::WriterBuilder::new()
+        .delimiter(delimiter)
+        .from_writer(out);
+
+    writer.write_record(CHANGE_HEADERS)?;
+    for change in changes {
+        let from = change.from.as_deref().unwrap_or(Path::new(""));
+        writer.write_record([
+            change.kind.as_str().as_bytes(),
+            change.path.as_os_str().as_encoded_bytes(),
+            from.as_os_str().as_encoded_bytes(),
+            &size(change.old_size),
+            &size(change.new_size),
+        ])?;
+    }
+
+    writer.flush()
+}
+
+/// Plain lines start with `+` added, `-` removed, `>` moved or `~` resized
+pub fn write_changes<W: Write>(
+    mut out: W,
+    format: OutputFormat,
+    changes: &[Change],
+) -> io::Result<()> {
+    match format {
+        OutputFormat::Plain => {
+            for change in changes {
+                let path = change.path.to_string_lossy();
+                let size = |size: Option<u64>| human_size(size.unwrap_or_default());
+                match (change.kind, &change.from) {
+                    (ChangeKind::Added, _) => writeln!(out, "+ {path}")?,
+                    (ChangeKind::Removed, _) => writeln!(out, "- {path}")?,
+                    (ChangeKind::Moved, Some(from)) => {
+                        writeln!(out, "> {} -> {path}", from.to_string_lossy())?
+                    }
+                    (ChangeKind::Moved, None) => writeln!(out, "> {path}")?,
+                    (ChangeKind::Resized, _) => writeln!(
+                        out,
+                        "~ {path} ({} -> {})",
+                        size(change.old_size),
+                        size(change.new_size)
+                    )?,
+                }
+            }
+        }
+        OutputFormat::Json => {
+            serde_json::to_writer(&mut out, changes)?;
+            writeln!(out)?;
+        }
+        OutputFormat::Jsonl => {
+            for change in changes {
+                serde_json::to_writer(&mut out, change)?;
+                writeln!(out)?;
+            }
+        }
+        OutputFormat::Csv => write_delimited_changes(&mut out, b',', changes)?,
+        OutputFormat::Tsv => write_delimited_changes(&mut out, b'\t', changes)?,
+        OutputFormat::Null => {
+            for change in changes {
+                out.write_all(change.path.as_os_str().as_encoded_bytes())?;
+                out.write_all(&[0])?;
+            }
+        }
+    }
+
+    out.flush()
+}
+
+pub fn human_size(size: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KiB", "MiB", "GiB", "TiB"];
+
+    let mut size = size as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{size} {}", UNITS[unit])
+    } else {
+        format!("{size:.1} {}", UNITS[unit])
+    }
+}
+
+/// `duration` in the largest unit it holds, whole except for seconds
+pub fn human_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    match secs {
+        0 => format!("{} ms", duration.as_millis()),
+        1..60 => format!("{:.1} s", duration.as_secs_f64()),
+        60..3600 => format!("{} min", secs / 60),
+        3600..86400 => format!("{} h", secs / 3600),
+        _ => format!("{} days", secs / 86400),
+    }
+}
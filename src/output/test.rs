@@ -0,0 +1,123 @@
+use std::path::PathBuf;
+
+use crate::file::{File, FileKind};
+
+use crate::search::{LineMatch, SearchHit};
+
+use super::{write_results, OutputFormat, ResultRecord};
+
+fn sample(path: PathBuf) -> ResultRecord {
+    let file = File {
+        name: "main.rs".to_string(),
+        kind: FileKind::File,
+        size: 42,
+        created: Some(1_700_000_000),
+        modified: Some(1_700_000_100),
+        accessed: None,
+        attributes: 0,
+        id: 0,
+        children: vec![],
+    };
+
+    ResultRecord::new(&file, path)
+}
+
+fn render(format: OutputFormat, results: &[ResultRecord]) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_results(&mut out, format, results, false).unwrap();
+    out
+}
+
+#[test]
+fn jsonl_output() {
+    let out = render(
+        OutputFormat::Jsonl,
+        &[sample(PathBuf::from("caver/src/main.rs"))],
+    );
+
+    let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(value["name"], "main.rs");
+    assert_eq!(value["path"], "caver/src/main.rs");
+    assert_eq!(value["kind"], "file");
+    assert_eq!(value["size"], 42);
+    assert_eq!(value["modified"], 1_700_000_100);
+    assert!(value["accessed"].is_null());
+}
+
+#[test]
+fn csv_output() {
+    let out = render(OutputFormat::Csv, &[sample(PathBuf::from("a,b/main.rs"))]);
+
+    pretty_assertions::assert_eq!(
+        String::from_utf8(out).unwrap(),
+        "name,path,kind,size,created,modified,accessed\n\
+         main.rs,\"a,b/main.rs\",file,42,1700000000,1700000100,\n"
+    );
+}
+
+#[test]
+fn null_output() {
+    let out = render(
+        OutputFormat::Null,
+        &[sample(PathBuf::from("a b")), sample(PathBuf::from("c"))],
+    );
+
+    assert_eq!(out, b"a b\0c\0");
+}
+
+#[cfg(unix)]
+#[test]
+fn non_utf8_path() {
+    use std::{ffi::OsStr, os::unix::ffi::OsStrExt};
+
+    let record = sample(PathBuf::from(OsStr::from_bytes(b"caver/\xffmain.rs")));
+
+    let out = render(OutputFormat::Null, std::slice::from_ref(&record));
+    assert_eq!(out, b"caver/\xffmain.rs\0");
+
+    let out = render(OutputFormat::Json, std::slice::from_ref(&record));
+    let value: serde_json::Value = serde_json::from_slice(&out).unwrap();
+    assert_eq!(value[0]["path"]["bytes"], "Y2F2ZXIv/21haW4ucnM=");
+
+    let parsed: Vec<ResultRecord> = serde_json::from_slice(&out).unwrap();
+    assert_eq!(parsed, vec![record]);
+}
+
+#[test]
+fn plain_hits() {
+    let range = |start, end| start..end;
+    let line = |line: usize, text: &str, before: &[&str], after: &[&str]| LineMatch {
+        line,
+        column: 1,
+        text: text.to_string(),
+        ranges: vec![range(0, 2)],
+        before: before.iter().map(|s| s.to_string()).collect(),
+        after: after.iter().map(|s| s.to_string()).collect(),
+    };
+    let mut record = sample(PathBuf::from("src/main.rs"));
+    record.hit = Some(SearchHit {
+        name: vec![range(0, 4)],
+        path: vec![range(4, 8)],
+        lines: vec![
+            line(2, "fn a", &["one"], &["fn b"]),
+            line(3, "fn b", &["fn a"], &["two"]),
+            line(9, "fn c", &["x"], &[]),
+        ],
+    });
+
+    let mut out = Vec::new();
+    write_results(&mut out, OutputFormat::Plain, &[record.clone()], true).unwrap();
+    assert!(String::from_utf8(out)
+        .unwrap()
+        .starts_with("src/\x1b[1;31mmain\x1b[0m.rs\n"));
+
+    pretty_assertions::assert_eq!(
+        String::from_utf8(render(OutputFormat::Plain, &[record.clone()])).unwrap(),
+        "src/main.rs\n1-one\n2:1:fn a\n3:1:fn b\n4-two\n--\n8-x\n9:1:fn c\n\n"
+    );
+
+    let value: serde_json::Value =
+        serde_json::from_slice(&render(OutputFormat::Jsonl, &[record])).unwrap();
+    assert_eq!(value["hit"]["name"][0]["end"], 4);
+    assert_eq!(value["hit"]["lines"][2]["line"], 9);
+}
@@ -0,0 +1,53 @@
+//! Progress of an indexing, reported through a callback so the caller decides how to show it
+
+use std::fmt::{self, Display};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Phase {
+    /// Loading the MFT of a disk in memory
+    ReadingMft,
+    /// Turning the MFT records into the file tree
+    Records,
+    /// Walking the directories of a disk without MFT
+    Walking,
+    Done,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            Self::ReadingMft => "reading mft",
+            Self::Records => "indexing records",
+            Self::Walking => "walking",
+            Self::Done => "done",
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress<'a> {
+    /// Name of the disk or walked directory
+    pub source: &'a str,
+    pub phase: Phase,
+    /// Records or files processed so far
+    pub done: u64,
+    /// Unknown while walking
+    pub total: Option<u64>,
+}
+
+impl Display for Progress<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} : {}", self.source, self.phase)?;
+        match self.total {
+            Some(total) => write!(f, " {}/{}", self.done, total),
+            None if self.done > 0 => write!(f, " {}", self.done),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Called from the indexing threads, possibly from several at once
+pub type ProgressFn<'a> = &'a (dyn Fn(Progress) + Sync);
+
+/// Number of records or files between two reports
+pub(crate) const STEP: u64 = 1 << 14;
@@ -0,0 +1,84 @@
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use tracing::debug;
+
+use crate::config;
+
+/// Names of the ignore files read in every directory
+pub const IGNORE_FILES: [&str; 2] = [".gitignore", ".ignore"];
+
+/// Ignore files applying to a directory, from the global one to the deepest
+#[derive(Debug, Clone, Default)]
+pub struct Ignores(Vec<Arc<Gitignore>>);
+
+fn build(root: &Path, files: impl IntoIterator<Item = PathBuf>) -> Option<Arc<Gitignore>> {
+    let mut builder = GitignoreBuilder::new(root);
+    for file in files {
+        if let Some(e) = builder.add(&file) {
+            debug!(path = %file.display(), "ignore file partially read : {e}");
+        }
+    }
+
+    match builder.build() {
+        Ok(gitignore) if !gitignore.is_empty() => Some(Arc::new(gitignore)),
+        Ok(_) => None,
+        Err(e) => {
+            debug!(path = %root.display(), "ignore files skipped : {e}");
+            None
+        }
+    }
+}
+
+impl Ignores {
+    /// Caver's own ignore file, in gitignore syntax, applying to every source
+    pub fn global_path() -> Option<PathBuf> {
+        config::dir().map(|dir| dir.join("ignore"))
+    }
+
+    /// The global ignore file at `global`, with patterns anchored at `root`
+    pub fn new(root: &Path, global: Option<&Path>) -> Self {
+        let global = global.filter(|path| path.exists());
+        Self(
+            global
+                .and_then(|path| build(root, [path.to_path_buf()]))
+                .into_iter()
+                .collect(),
+        )
+    }
+
+    /// The ignores of the children of `dir`, adding its ignore files when `has_file` finds them
+    pub fn enter(&self, dir: &Path, has_file: impl Fn(&str) -> bool) -> Self {
+        let files = IGNORE_FILES
+            .iter()
+            .filter(|name| has_file(name))
+            .map(|name| dir.join(name))
+            .collect::<Vec<_>>();
+
+        if files.is_empty() {
+            return self.clone();
+        }
+
+        let mut ignores = self.clone();
+        ignores.0.extend(build(dir, files));
+        ignores
+    }
+
+    /// Deeper ignore files take precedence, like in git
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        for gitignore in self.0.iter().rev() {
+            match gitignore.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(_) => return true,
+                Match::Whitelist(_) => return false,
+            }
+        }
+        false
+    }
+}
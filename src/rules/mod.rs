@@ -0,0 +1,232 @@
+mod ignore_files;
+#[cfg(test)]
+mod test;
+
+use std::{borrow::Cow, fs, path::Path, path::PathBuf};
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    config,
+    error::{CaverError, CaverResult},
+    file::{attributes, File},
+};
+
+pub use ignore_files::{Ignores, IGNORE_FILES};
+
+/// Which files make it into the index, read from `rules.toml` in the config directory.
+///
+/// Patterns without a path separator are matched against file names (`node_modules`, `*.tmp`),
+/// the others against full paths (`C:\Windows\WinSxS`, `/home/*/.cache`).
+/// Relative paths (`src/generated`) match at any depth, like `**/src/generated`.
+/// An excluded directory is left out with everything under it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Rules {
+    pub exclude: Vec<String>,
+    /// Same patterns as `exclude`, a file matching both is indexed
+    pub include: Vec<String>,
+    /// Index hidden files, dot files on unix
+    pub hidden: bool,
+    /// Index files with the system attribute
+    pub system: bool,
+    /// Leave out the files matched by `.gitignore`, `.ignore` or the global ignore file,
+    /// otherwise they are indexed and can be filtered with `ignored<no>`
+    pub gitignore: bool,
+    /// List the entries of zip, jar, nupkg, tar and tar.gz archives in a directory next to them,
+    /// `foo.zip!`, whose files can be searched like the others
+    pub archives: bool,
+}
+
+impl Default for Rules {
+    fn default() -> Self {
+        Self {
+            exclude: ["$Recycle.Bin", "System Volume Information", "WinSxS", "node_modules"]
+                .map(String::from)
+                .to_vec(),
+            include: vec![],
+            hidden: true,
+            system: true,
+            gitignore: false,
+            archives: false,
+        }
+    }
+}
+
+fn is_path_pattern(pattern: &str) -> bool {
+    pattern.contains('/') || (cfg!(windows) && pattern.contains('\\'))
+}
+
+/// Relative path patterns match below any directory
+fn is_absolute(pattern: &str) -> bool {
+    pattern.starts_with(['/', '\\']) || Path::new(pattern).is_absolute()
+}
+
+/// The glob matched against full paths for a path pattern
+fn path_glob(pattern: &str) -> Cow<'_, str> {
+    match is_absolute(pattern) || pattern.starts_with("**/") {
+        true => Cow::Borrowed(pattern),
+        false => Cow::Owned(format!("**/{pattern}")),
+    }
+}
+
+/// Paths are compared without case on windows, like the file system does
+fn normalize(path: &str) -> String {
+    if cfg!(windows) {
+        path.replace('/', "\\").to_lowercase()
+    } else {
+        path.to_string()
+    }
+}
+
+impl Rules {
+    pub fn from_toml(s: &str) -> CaverResult<Self> {
+        toml::from_str(s).map_err(CaverError::InvalidRules)
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        config::dir().map(|dir| dir.join("rules.toml"))
+    }
+
+    /// Loads the user's rules file, or the defaults if there is none
+    pub fn load() -> CaverResult<Self> {
+        config::load(Self::path(), Self::from_toml)
+    }
+
+    /// The rules that can change what is indexed under `root`, with the global ignore file,
+    /// an index built with the same ones doesn't need to be rebuilt
+    pub fn relevant_to(&self, root: &str) -> RelevantRules {
+        self.relevant_with(root, Ignores::global_path().as_deref())
+    }
+
+    fn relevant_with(&self, root: &str, global_ignore: Option<&Path>) -> RelevantRules {
+        let root = normalize(root);
+        let relevant = |pattern: &&String| {
+            !is_path_pattern(pattern)
+                || !is_absolute(pattern)
+                || normalize(pattern).starts_with(&root)
+        };
+
+        RelevantRules {
+            rules: Self {
+                exclude: self.exclude.iter().filter(relevant).cloned().collect(),
+                include: self.include.iter().filter(relevant).cloned().collect(),
+                hidden: self.hidden,
+                system: self.system,
+                gitignore: self.gitignore,
+                archives: self.archives,
+            },
+            global_ignore: global_ignore
+                .filter(|_| self.gitignore)
+                .and_then(|path| fs::read_to_string(path).ok()),
+        }
+    }
+}
+
+/// The part of the [`Rules`] that applied to a root, see [`Rules::relevant_to`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RelevantRules {
+    pub rules: Rules,
+    /// Contents of the global ignore file, when [`Rules::gitignore`] reads it
+    pub global_ignore: Option<String>,
+}
+
+/// Patterns of a [`Rules`] compiled to be matched against every file
+#[derive(Debug, Clone)]
+pub struct Filter {
+    hidden: bool,
+    system: bool,
+    gitignore: bool,
+    /// See [`Ignores::global_path`]
+    global_ignore: Option<PathBuf>,
+    exclude_names: GlobSet,
+    exclude_paths: GlobSet,
+    include_names: GlobSet,
+    include_paths: GlobSet,
+}
+
+fn glob_sets(patterns: &[String]) -> CaverResult<(GlobSet, GlobSet)> {
+    let mut names = GlobSetBuilder::new();
+    let mut paths = GlobSetBuilder::new();
+
+    for pattern in patterns {
+        let glob = match is_path_pattern(pattern) {
+            true => path_glob(pattern),
+            false => Cow::Borrowed(pattern.as_str()),
+        };
+        let glob = GlobBuilder::new(&glob)
+            .case_insensitive(cfg!(windows))
+            .literal_separator(true)
+            .build()
+            .map_err(CaverError::InvalidPattern)?;
+
+        if is_path_pattern(pattern) {
+            paths.add(glob);
+        } else {
+            names.add(glob);
+        }
+    }
+
+    Ok((
+        names.build().map_err(CaverError::InvalidPattern)?,
+        paths.build().map_err(CaverError::InvalidPattern)?,
+    ))
+}
+
+impl Filter {
+    pub fn new(rules: &Rules) -> CaverResult<Self> {
+        let (exclude_names, exclude_paths) = glob_sets(&rules.exclude)?;
+        let (include_names, include_paths) = glob_sets(&rules.include)?;
+
+        Ok(Self {
+            hidden: rules.hidden,
+            system: rules.system,
+            gitignore: rules.gitignore,
+            global_ignore: Ignores::global_path(),
+            exclude_names,
+            exclude_paths,
+            include_names,
+            include_paths,
+        })
+    }
+
+    /// Reads the global ignore file from `global_ignore` instead of the config directory
+    pub fn with_global_ignore(self, global_ignore: Option<PathBuf>) -> Self {
+        Self {
+            global_ignore,
+            ..self
+        }
+    }
+
+    /// The ignore files applying to everything under `root`, before its own are read
+    pub fn ignores(&self, root: &Path) -> Ignores {
+        Ignores::new(root, self.global_ignore.as_deref())
+    }
+
+    /// Whether `file`, found at `path`, goes in the index
+    pub fn keeps(&self, path: &Path, file: &File) -> bool {
+        let excluded = (!self.hidden && file.is_hidden())
+            || (!self.system && file.is_system())
+            || self.exclude_names.is_match(&file.name)
+            || self.exclude_paths.is_match(path);
+
+        !excluded || self.include_names.is_match(&file.name) || self.include_paths.is_match(path)
+    }
+
+    /// Marks `file` as ignored when an ignore file matches it or its directory is ignored.
+    /// Returns false when ignored files are left out of the index
+    pub fn mark_ignored(
+        &self,
+        path: &Path,
+        file: &mut File,
+        ignores: &Ignores,
+        parent_ignored: bool,
+    ) -> bool {
+        if parent_ignored || ignores.is_ignored(path, file.is_dir()) {
+            file.attributes |= attributes::IGNORED;
+        }
+
+        !(self.gitignore && file.is_ignored())
+    }
+}
@@ -0,0 +1,93 @@
+use std::{fs, path::Path};
+
+use crate::{
+    file::{attributes, File, FileKind},
+    test_util::temp_dir,
+};
+
+use super::{Filter, Rules};
+
+fn file(name: &str) -> File {
+    File {
+        name: name.to_string(),
+        kind: FileKind::Directory,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn default_rules() {
+    let filter = Filter::new(&Rules::default()).unwrap();
+
+    assert!(!filter.keeps(Path::new("/home/me/app/node_modules"), &file("node_modules")));
+    assert!(!filter.keeps(Path::new("/$Recycle.Bin"), &file("$Recycle.Bin")));
+    assert!(filter.keeps(Path::new("/home/me/app/src"), &file("src")));
+}
+
+#[test]
+fn rules_file() {
+    let rules = Rules::from_toml(
+        r#"
+        exclude = ["*.tmp", "/home/*/.cache"]
+        include = ["keep.tmp"]
+        hidden = false
+        "#,
+    )
+    .unwrap();
+    assert!(rules.system);
+
+    let filter = Filter::new(&rules).unwrap();
+    assert!(!filter.keeps(Path::new("/a/b.tmp"), &file("b.tmp")));
+    assert!(filter.keeps(Path::new("/a/keep.tmp"), &file("keep.tmp")));
+    assert!(!filter.keeps(Path::new("/home/me/.cache"), &file(".cache")));
+    // Separators aren't crossed by a single `*`
+    assert!(filter.keeps(Path::new("/home/me/x/cache"), &file("cache")));
+
+    let hidden = File {
+        attributes: attributes::HIDDEN,
+        ..file("secret")
+    };
+    assert!(!filter.keeps(Path::new("/a/secret"), &hidden));
+
+    assert!(Rules::from_toml("exclude = 3").is_err());
+    assert!(Rules::from_toml("excludes = []").is_err());
+}
+
+#[test]
+fn relevant_rules() {
+    let rules = Rules {
+        exclude: vec![
+            "*.tmp".to_string(),
+            "/mnt/a/build".to_string(),
+            "/mnt/b/build".to_string(),
+            "**/target".to_string(),
+            "src/generated".to_string(),
+        ],
+        ..Default::default()
+    };
+
+    assert_eq!(
+        rules.relevant_to("/mnt/a").rules.exclude,
+        ["*.tmp", "/mnt/a/build", "**/target", "src/generated"]
+    );
+
+    let filter = Filter::new(&rules).unwrap();
+    let generated = Path::new("/mnt/b/app/src/generated");
+    assert!(!filter.keeps(generated, &file("generated")));
+    assert!(filter.keeps(Path::new("/mnt/b/generated"), &file("generated")));
+
+    // Editing the global ignore file makes the index outdated when it is read
+    let dir = temp_dir("relevant");
+    let global = dir.join("ignore");
+    fs::write(&global, "*.log\n").unwrap();
+    let before = rules.relevant_with("/mnt/a", Some(&global));
+    assert_eq!(before.global_ignore, None);
+
+    let rules = Rules {
+        gitignore: true,
+        ..rules
+    };
+    let before = rules.relevant_with("/mnt/a", Some(&global));
+    fs::write(&global, "*.log\n*.bak\n").unwrap();
+    assert_ne!(rules.relevant_with("/mnt/a", Some(&global)), before);
+}
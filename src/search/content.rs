@@ -0,0 +1,286 @@
+use std::{
+    fs,
+    io::{self, Cursor, Read},
+    path::Path,
+};
+
+use crate::file::{archive, attributes, File};
+
+use super::{SearchExpr, SearchExprValue};
+
+const CHUNK: usize = 1 << 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+}
+
+impl Encoding {
+    /// The encoding given by the byte order mark at the start of `bytes`, and its length
+    fn from_bom(bytes: &[u8]) -> Option<(Self, usize)> {
+        match bytes {
+            [0xEF, 0xBB, 0xBF, ..] => Some((Self::Utf8, 3)),
+            [0xFF, 0xFE, ..] => Some((Self::Utf16Le, 2)),
+            [0xFE, 0xFF, ..] => Some((Self::Utf16Be, 2)),
+            _ => None,
+        }
+    }
+}
+
+/// Turns chunks of bytes into text, invalid sequences are replaced by `�`
+struct Decoder {
+    encoding: Encoding,
+    /// The start of a character cut by the end of the last chunk
+    pending: Vec<u8>,
+}
+
+impl Decoder {
+    fn decode(&mut self, bytes: &[u8], last: bool, out: &mut String) {
+        self.pending.extend_from_slice(bytes);
+        let mut bytes = std::mem::take(&mut self.pending);
+
+        let used = match self.encoding {
+            Encoding::Utf8 => decode_utf8(&bytes, last, out),
+            Encoding::Utf16Le => decode_utf16(&bytes, last, u16::from_le_bytes, out),
+            Encoding::Utf16Be => decode_utf16(&bytes, last, u16::from_be_bytes, out),
+        };
+
+        bytes.drain(..used);
+        self.pending = bytes;
+    }
+}
+
+/// Returns how many bytes were decoded
+fn decode_utf8(mut bytes: &[u8], last: bool, out: &mut String) -> usize {
+    let len = bytes.len();
+    loop {
+        match std::str::from_utf8(bytes) {
+            Ok(s) => {
+                out.push_str(s);
+                return len;
+            }
+            Err(e) => {
+                let (valid, rest) = bytes.split_at(e.valid_up_to());
+                out.push_str(std::str::from_utf8(valid).unwrap());
+
+                match e.error_len() {
+                    Some(invalid) => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        bytes = &rest[invalid..];
+                    }
+                    // Cut by the end of the chunk
+                    None if !last => return len - rest.len(),
+                    None => {
+                        out.push(char::REPLACEMENT_CHARACTER);
+                        return len;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn decode_utf16(
+    bytes: &[u8],
+    last: bool,
+    from_bytes: fn([u8; 2]) -> u16,
+    out: &mut String,
+) -> usize {
+    let mut units = bytes
+        .chunks_exact(2)
+        .map(|unit| from_bytes([unit[0], unit[1]]))
+        .collect::<Vec<_>>();
+
+    // A high surrogate waits for the other half of its pair
+    let mut used = units.len() * 2;
+    if !last
+        && units
+            .last()
+            .is_some_and(|unit| (0xD800..0xDC00).contains(unit))
+    {
+        units.pop();
+        used -= 2;
+    }
+
+    out.extend(char::decode_utf16(units).map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER)));
+    if last && used < bytes.len() {
+        out.push(char::REPLACEMENT_CHARACTER);
+        return bytes.len();
+    }
+    used
+}
+
+pub(super) fn collect_values<'e>(expr: &'e SearchExpr, out: &mut Vec<&'e str>) {
+    match &expr.expr {
+        SearchExprValue::Operation(op) => {
+            collect_values(&op.lhs, out);
+            collect_values(&op.rhs, out);
+        }
+        SearchExprValue::Value(value) => out.push(value),
+    }
+}
+
+/// Files with a NUL in their first [`CHUNK`] bytes are binary, unless they are UTF-16
+fn is_binary(encoding: Encoding, bytes: &[u8]) -> bool {
+    encoding == Encoding::Utf8 && bytes[..bytes.len().min(CHUNK)].contains(&0)
+}
+
+/// Reads until `buf` is full or `reader` ends, returns how many bytes were read
+fn fill(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..]) {
+            Ok(0) => break,
+            Ok(n) => read += n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(read)
+}
+
+/// Reads the file in chunks until `expr` is decided, binary files never match
+fn matches_reader(mut reader: impl Read, expr: &SearchExpr) -> io::Result<bool> {
+    let mut values = Vec::new();
+    collect_values(expr, &mut values);
+    let mut found = vec![false; values.len()];
+    // Kept from the last chunk to find values cut in two
+    let overlap = values.iter().map(|value| value.len()).max().unwrap_or(0);
+
+    let is_found = |found: &[bool], value: &str| {
+        values
+            .iter()
+            .zip(found)
+            .any(|(v, &found)| found && *v == value)
+    };
+
+    let mut buf = vec![0; CHUNK];
+    let mut read = fill(&mut reader, &mut buf)?;
+    let (encoding, mut start) = Encoding::from_bom(&buf[..read]).unwrap_or((Encoding::Utf8, 0));
+    if is_binary(encoding, &buf[..read]) {
+        return Ok(false);
+    }
+
+    let mut decoder = Decoder {
+        encoding,
+        pending: Vec::new(),
+    };
+    let mut text = String::new();
+    loop {
+        let last = read < buf.len();
+        decoder.decode(&buf[start..read], last, &mut text);
+        for (value, found) in values.iter().zip(found.iter_mut()) {
+            *found |= text.contains(value);
+        }
+
+        let decided = expr.reach(&|value| is_found(&found, value).then_some(true));
+        if last || decided.is_some() {
+            return Ok(expr.matches(&|value| is_found(&found, value)));
+        }
+
+        let mut keep = text.len().saturating_sub(overlap);
+        while !text.is_char_boundary(keep) {
+            keep -= 1;
+        }
+        text.drain(..keep);
+
+        read = fill(&mut reader, &mut buf)?;
+        start = 0;
+    }
+}
+
+/// Calls `f` with each line of the text of `reader`, without its line ending,
+/// never holding more than a chunk and a line. `Ok(false)` for binary files, `f` isn't called
+pub(super) fn for_each_line(mut reader: impl Read, mut f: impl FnMut(&str)) -> io::Result<bool> {
+    let mut buf = vec![0; CHUNK];
+    let mut read = fill(&mut reader, &mut buf)?;
+    let (encoding, mut start) = Encoding::from_bom(&buf[..read]).unwrap_or((Encoding::Utf8, 0));
+    if is_binary(encoding, &buf[..read]) {
+        return Ok(false);
+    }
+
+    let mut decoder = Decoder {
+        encoding,
+        pending: Vec::new(),
+    };
+    let mut text = String::new();
+    loop {
+        let last = read < buf.len();
+        decoder.decode(&buf[start..read], last, &mut text);
+
+        // The last line may go on in the next chunk
+        let complete = match last {
+            true => text.len(),
+            false => text.rfind('\n').map_or(0, |i| i + 1),
+        };
+        text[..complete].lines().for_each(&mut f);
+        text.drain(..complete);
+
+        if last {
+            return Ok(true);
+        }
+        read = fill(&mut reader, &mut buf)?;
+        start = 0;
+    }
+}
+
+/// The file to read for `file` at `path`, `None` for directories, files that aren't regular,
+/// larger than `max_size` or not on the disk
+fn open(file: &File, path: &Path, max_size: Option<u64>) -> Option<Box<dyn Read>> {
+    let too_large = max_size.is_some_and(|max| file.size > max);
+    if file.is_dir() || file.attributes & attributes::NOT_ON_DISK != 0 || too_large {
+        return None;
+    }
+
+    open_path(path, max_size)
+}
+
+/// The regular file at `path`, `None` if it is larger than `max_size` or can't be opened
+pub(super) fn open_path(path: &Path, max_size: Option<u64>) -> Option<Box<dyn Read>> {
+    // The index may be outdated, and symlinks are indexed as files
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        // Or it is in an archive
+        let bytes = archive::read_entry(path, max_size)?;
+        return Some(Box::new(Cursor::new(bytes)));
+    };
+    if !metadata.is_file() || max_size.is_some_and(|max| metadata.len() > max) {
+        return None;
+    }
+
+    Some(Box::new(fs::File::open(path).ok()?))
+}
+
+/// Whether the content of `file` at `path` matches `expr`, files that can't be read don't.
+/// Directories, files that aren't regular, larger than `max_size` or not on the disk are skipped
+pub(super) fn matches(file: &File, path: &Path, expr: &SearchExpr, max_size: Option<u64>) -> bool {
+    open(file, path, max_size)
+        .and_then(|file| matches_reader(file, expr).ok())
+        .unwrap_or(false)
+}
+
+/// All of the text, `None` for binary files
+fn read_reader(mut reader: impl Read) -> io::Result<Option<String>> {
+    let mut bytes = Vec::new();
+    reader.read_to_end(&mut bytes)?;
+
+    let (encoding, bom) = Encoding::from_bom(&bytes).unwrap_or((Encoding::Utf8, 0));
+    if is_binary(encoding, &bytes) {
+        return Ok(None);
+    }
+
+    let mut text = String::new();
+    Decoder {
+        encoding,
+        pending: Vec::new(),
+    }
+    .decode(&bytes[bom..], true, &mut text);
+    Ok(Some(text))
+}
+
+/// The text of `file` at `path` as [`matches`] sees it, `Some(None)` for binary files.
+/// `None` if it is skipped or can't be read
+pub(super) fn read(file: &File, path: &Path, max_size: Option<u64>) -> Option<Option<String>> {
+    read_reader(open(file, path, max_size)?).ok()
+}
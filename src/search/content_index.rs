@@ -0,0 +1,377 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    path::{Path, PathBuf},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::{
+    config, db,
+    error::{CaverError, CaverResult},
+    file::{
+        index::{strip_root, FileIndex},
+        File,
+    },
+};
+
+use super::{
+    content, plan,
+    trigram::{trigram, CHAR_BITS},
+    SearchExpr,
+};
+
+/// Which files go in the [`ContentIndex`], read from `content.toml` in the config directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ContentRules {
+    /// Directories whose files are indexed, none by default
+    pub paths: Vec<String>,
+    /// Extensions of the indexed files, without the dot, any if empty
+    pub extensions: Vec<String>,
+    /// Larger files are left out, and read by the searches instead
+    pub max_size: u64,
+}
+
+impl Default for ContentRules {
+    fn default() -> Self {
+        Self {
+            paths: vec![],
+            extensions: [
+                "txt", "md", "rst", "log", "csv", "json", "toml", "yaml", "yml", "xml", "html",
+                "css", "js", "ts", "py", "rs", "go", "c", "h", "cpp", "hpp", "cs", "java", "kt",
+                "sh", "ps1", "bat", "ini", "cfg", "sql",
+            ]
+            .map(String::from)
+            .to_vec(),
+            max_size: 1 << 20,
+        }
+    }
+}
+
+impl ContentRules {
+    pub fn from_toml(s: &str) -> CaverResult<Self> {
+        toml::from_str(s).map_err(CaverError::InvalidContentRules)
+    }
+
+    pub fn path() -> Option<PathBuf> {
+        config::dir().map(|dir| dir.join("content.toml"))
+    }
+
+    /// Loads the user's content rules, or the defaults if there are none
+    pub fn load() -> CaverResult<Self> {
+        config::load(Self::path(), Self::from_toml)
+    }
+
+    fn keeps(&self, file: &File) -> bool {
+        let extension = Path::new(&file.name).extension();
+        !file.is_dir()
+            && file.size <= self.max_size
+            && (self.extensions.is_empty()
+                || extension.is_some_and(|extension| {
+                    (self.extensions.iter()).any(|e| extension.eq_ignore_ascii_case(e.as_str()))
+                }))
+    }
+}
+
+/// Where a file was when it was read, it is read again when either changes
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Doc {
+    id: u32,
+    size: u64,
+    modified: Option<i64>,
+    binary: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Posting {
+    doc: u32,
+    /// Sorted, in characters from the start of the text
+    positions: Vec<u32>,
+}
+
+/// What [`ContentIndex::update`] did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ContentUpdate {
+    /// New or changed files that were read
+    pub read: usize,
+    pub removed: usize,
+    pub unchanged: usize,
+}
+
+/// Positions of every trigram in the text of the files matching [`ContentRules`],
+/// answering `content<...>` without reading them
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ContentIndex {
+    version: u32,
+    docs: HashMap<PathBuf, Doc>,
+    /// Ids of removed files, given to the next ones
+    free: Vec<u32>,
+    next_id: u32,
+    /// By trigram, see [`trigram`], sorted by doc
+    postings: BTreeMap<u64, Vec<Posting>>,
+}
+
+/// Positions of each trigram of `text`, padded with NULs so that every character starts one.
+/// NUL never appears in an indexed text, binary files are left out
+fn trigrams(text: &str) -> HashMap<u64, Vec<u32>> {
+    let chars = text.chars().chain(['\0'; 2]).collect::<Vec<_>>();
+    let mut positions = HashMap::<_, Vec<_>>::new();
+    for (i, window) in chars.windows(3).enumerate() {
+        positions
+            .entry(trigram([window[0], window[1], window[2]]))
+            .or_default()
+            .push(i as u32);
+    }
+    positions
+}
+
+fn positions(postings: &[Posting], doc: u32) -> Option<&[u32]> {
+    let i = postings.binary_search_by_key(&doc, |p| p.doc).ok()?;
+    Some(&postings[i].positions)
+}
+
+/// Matches of a query against the index, see [`ContentIndex::lookup`]
+pub(crate) struct Lookup<'a> {
+    index: &'a ContentIndex,
+    expr: &'a SearchExpr,
+    /// The files containing each value
+    found: HashMap<&'a str, HashSet<u32>>,
+}
+
+impl Lookup<'_> {
+    /// Whether `file` at `path` matches, the same as reading it would tell:
+    /// binary files are told apart the same way when the index reads them.
+    /// `None` if the index doesn't hold it or it changed since
+    pub(crate) fn matches(&self, file: &File, path: &Path, max_size: Option<u64>) -> Option<bool> {
+        let doc = self.index.docs.get(path)?;
+        if doc.size != file.size || doc.modified != file.modified {
+            return None;
+        }
+
+        if doc.binary || max_size.is_some_and(|max| file.size > max) {
+            return Some(false);
+        }
+        Some(
+            self.expr
+                .matches(&|value| self.found.get(value).is_some_and(|f| f.contains(&doc.id))),
+        )
+    }
+}
+
+impl Default for ContentIndex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ContentIndex {
+    pub const SAVE_PATH: &'static str = "target/content.db";
+
+    /// Bumped every time the serialized layout of the index changes
+    pub const VERSION: u32 = 1;
+
+    pub fn new() -> Self {
+        Self {
+            version: Self::VERSION,
+            docs: HashMap::new(),
+            free: Vec::new(),
+            next_id: 0,
+            postings: BTreeMap::new(),
+        }
+    }
+
+    /// How many files it holds
+    pub fn len(&self) -> usize {
+        self.docs.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.docs.is_empty()
+    }
+
+    /// Reads the files of `index` matching `rules` that are new or changed,
+    /// and drops the ones that are gone or no longer match
+    pub fn update(&mut self, index: &FileIndex, rules: &ContentRules) -> ContentUpdate {
+        let mut wanted = Vec::new();
+        for disk in index.disks() {
+            let mut iter = disk.iter();
+            while let Some(entry) = iter.next_entry() {
+                let path = entry.path.to_string_lossy();
+                let under = |dir: &String| strip_root(entry.path, dir).is_some();
+                let above = |dir: &String| strip_root(Path::new(dir), &path).is_some();
+                if rules.paths.iter().any(under) {
+                    if rules.keeps(entry.file) {
+                        wanted.push((entry.file, entry.path.to_path_buf()));
+                    }
+                } else if !rules.paths.iter().any(above) {
+                    iter.skip_children();
+                }
+            }
+        }
+
+        let mut update = ContentUpdate::default();
+        let (unchanged, changed): (Vec<_>, Vec<_>) =
+            wanted.into_iter().partition(|(file, path)| {
+                self.docs
+                    .get(path)
+                    .is_some_and(|doc| doc.size == file.size && doc.modified == file.modified)
+            });
+        update.unchanged = unchanged.len();
+
+        // Changed files are removed, then read again
+        let unchanged = (unchanged.into_iter())
+            .map(|(_, path)| path)
+            .collect::<HashSet<_>>();
+        let outdated = (self.docs.keys())
+            .filter(|path| !unchanged.contains(*path))
+            .cloned()
+            .collect::<Vec<_>>();
+        // Not counting the changed ones, read again below
+        let held = (changed.iter())
+            .filter(|(_, path)| self.docs.contains_key(path))
+            .count();
+        update.removed = outdated.len() - held;
+        self.remove(&outdated);
+
+        let read = plan::io_pool().install(|| {
+            changed
+                .into_par_iter()
+                .filter_map(|(file, path)| {
+                    let text = content::read(file, &path, Some(rules.max_size))?;
+                    let trigrams = text.as_deref().map(trigrams).unwrap_or_default();
+                    Some((file, path, text.is_none(), trigrams))
+                })
+                .collect::<Vec<_>>()
+        });
+        update.read = read.len();
+
+        let mut touched = HashSet::new();
+        for (file, path, binary, trigrams) in read {
+            let id = self.free.pop().unwrap_or_else(|| {
+                self.next_id += 1;
+                self.next_id - 1
+            });
+            for (trigram, positions) in trigrams {
+                let postings = self.postings.entry(trigram).or_default();
+                postings.push(Posting { doc: id, positions });
+                touched.insert(trigram);
+            }
+            let doc = Doc {
+                id,
+                size: file.size,
+                modified: file.modified,
+                binary,
+            };
+            self.docs.insert(path, doc);
+        }
+        for trigram in touched {
+            let postings = self.postings.get_mut(&trigram).unwrap();
+            postings.sort_unstable_by_key(|posting| posting.doc);
+        }
+
+        debug!(?update, "content index updated");
+        update
+    }
+
+    fn remove(&mut self, paths: &[PathBuf]) {
+        let ids = paths
+            .iter()
+            .filter_map(|path| self.docs.remove(path))
+            .map(|doc| doc.id)
+            .collect::<HashSet<_>>();
+        if ids.is_empty() {
+            return;
+        }
+
+        self.postings.retain(|_, postings| {
+            postings.retain(|posting| !ids.contains(&posting.doc));
+            !postings.is_empty()
+        });
+        self.free.extend(ids);
+    }
+
+    /// The text files containing `value`
+    fn containing(&self, value: &str) -> HashSet<u32> {
+        let chars = value.chars().collect::<Vec<_>>();
+        if chars.len() < 3 {
+            // Every character starts a trigram, those starting with `value` are a range
+            let mut start = [0; 3];
+            for (i, c) in chars.iter().enumerate() {
+                start[i] = *c as u64;
+            }
+            let shift = CHAR_BITS * (3 - chars.len() as u32);
+            let start = start.into_iter().fold(0, |key, c| (key << CHAR_BITS) | c);
+            let end = start + (1 << shift);
+
+            let mut docs = self
+                .postings
+                .range(start..end)
+                .flat_map(|(_, postings)| postings.iter().map(|posting| posting.doc))
+                .collect::<HashSet<_>>();
+            if chars.is_empty() {
+                // Empty files have no trigram but contain the empty string
+                docs.extend(
+                    self.docs
+                        .values()
+                        .filter(|doc| !doc.binary)
+                        .map(|doc| doc.id),
+                );
+            }
+            return docs;
+        }
+
+        let lists = chars
+            .windows(3)
+            .map(|window| {
+                self.postings
+                    .get(&trigram([window[0], window[1], window[2]]))
+            })
+            .collect::<Option<Vec<_>>>();
+        let Some(lists) = lists else {
+            return HashSet::new();
+        };
+
+        // The value is at `start` if each of its trigrams is at the following position
+        lists[0]
+            .iter()
+            .filter(|posting| {
+                let others = (lists[1..].iter())
+                    .map(|list| positions(list, posting.doc))
+                    .collect::<Option<Vec<_>>>();
+                others.is_some_and(|others| {
+                    posting.positions.iter().any(|start| {
+                        (others.iter().zip(1..))
+                            .all(|(positions, i)| positions.binary_search(&(start + i)).is_ok())
+                    })
+                })
+            })
+            .map(|posting| posting.doc)
+            .collect()
+    }
+
+    /// Finds the files containing each value of `expr`
+    pub(crate) fn lookup<'a>(&'a self, expr: &'a SearchExpr) -> Lookup<'a> {
+        let mut values = Vec::new();
+        content::collect_values(expr, &mut values);
+        let found = values
+            .into_iter()
+            .map(|value| (value, self.containing(value)))
+            .collect();
+        Lookup {
+            index: self,
+            expr,
+            found,
+        }
+    }
+
+    /// Replaces the saved index at once, see [`db::save`]
+    pub fn save(&self) -> CaverResult<()> {
+        db::save(Path::new(Self::SAVE_PATH), self)
+    }
+
+    pub fn fetch_from_db() -> CaverResult<Self> {
+        db::load(Path::new(Self::SAVE_PATH), Self::VERSION)
+    }
+}
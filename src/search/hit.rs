@@ -0,0 +1,149 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read},
+    ops::Range,
+    path::Path,
+};
+
+use serde::{Deserialize, Serialize};
+
+use super::{content, SearchExpr, SearchExprValue, SearchParams};
+
+/// Why a result matched, byte ranges in UTF-8 text
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SearchHit {
+    pub name: Vec<Range<usize>>,
+    /// Including the matches of the name, at the end of the path
+    pub path: Vec<Range<usize>>,
+    /// The lines matching `content<...>`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<LineMatch>,
+}
+
+/// A line matching `content<...>`, like `grep -n -C` prints it
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LineMatch {
+    /// From 1
+    pub line: usize,
+    /// Byte of the first match in the line, from 1
+    pub column: usize,
+    pub text: String,
+    pub ranges: Vec<Range<usize>>,
+    /// The lines around it, as many as asked when the file has them
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// The values a match can be shown for, the ones under a `?` only match by their absence
+fn shown_values<'e>(expr: &'e SearchExpr, inverted: bool, out: &mut Vec<&'e str>) {
+    let inverted = inverted ^ expr.inverted;
+    match &expr.expr {
+        SearchExprValue::Operation(op) => {
+            shown_values(&op.lhs, inverted, out);
+            shown_values(&op.rhs, inverted, out);
+        }
+        SearchExprValue::Value(value) if !inverted && !value.is_empty() => out.push(value),
+        SearchExprValue::Value(_) => {}
+    }
+}
+
+/// Where the values shown for `expr` are in `s`, sorted and merged
+fn ranges(expr: Option<&SearchExpr>, s: &str) -> Vec<Range<usize>> {
+    let mut values = Vec::new();
+    if let Some(expr) = expr {
+        shown_values(expr, false, &mut values);
+    }
+
+    merge(
+        values
+            .into_iter()
+            .flat_map(|value| s.match_indices(value))
+            .map(|(start, value)| start..start + value.len())
+            .collect(),
+    )
+}
+
+/// Sorts the ranges, joining the ones that overlap
+fn merge(mut ranges: Vec<Range<usize>>) -> Vec<Range<usize>> {
+    ranges.sort_by_key(|range| (range.start, range.end));
+
+    let mut merged: Vec<Range<usize>> = Vec::new();
+    for range in ranges {
+        match merged.last_mut() {
+            Some(last) if range.start <= last.end => last.end = last.end.max(range.end),
+            _ => merged.push(range),
+        }
+    }
+    merged
+}
+
+impl SearchParams {
+    /// Where the query matched the result named `name` at `path`.
+    /// The file is read line by line for the lines matching `content<...>` when `context` is set,
+    /// with that many lines around them
+    pub fn hit(&self, name: &str, path: &Path, context: Option<usize>) -> SearchHit {
+        let path_str = path.to_string_lossy();
+        let name_ranges = ranges(self.name.as_ref(), name);
+
+        let mut path_ranges = ranges(self.path.as_ref(), &path_str);
+        if path_str.ends_with(name) {
+            let offset = path_str.len() - name.len();
+            path_ranges
+                .extend((name_ranges.iter()).map(|range| range.start + offset..range.end + offset));
+            path_ranges = merge(path_ranges);
+        }
+
+        let lines = match (&self.content, context) {
+            (Some(expr), Some(context)) => content::open_path(path, self.max_content_size)
+                .and_then(|reader| line_matches(expr, reader, context).ok())
+                .unwrap_or_default(),
+            _ => vec![],
+        };
+
+        SearchHit {
+            name: name_ranges,
+            path: path_ranges,
+            lines,
+        }
+    }
+}
+
+/// Keeps the last `context` lines read, and the matches until they have their lines after
+fn line_matches(
+    expr: &SearchExpr,
+    reader: impl Read,
+    context: usize,
+) -> io::Result<Vec<LineMatch>> {
+    let mut matches = Vec::<LineMatch>::new();
+    let mut before = VecDeque::with_capacity(context + 1);
+    let mut number = 0;
+
+    content::for_each_line(reader, |line| {
+        number += 1;
+        // The later matches are the ones still missing lines after them
+        for previous in matches.iter_mut().rev() {
+            if previous.after.len() == context {
+                break;
+            }
+            previous.after.push(line.to_string());
+        }
+
+        let ranges = ranges(Some(expr), line);
+        if let Some(first) = ranges.first() {
+            matches.push(LineMatch {
+                line: number,
+                column: first.start + 1,
+                text: line.to_string(),
+                ranges,
+                before: before.iter().cloned().collect(),
+                after: vec![],
+            });
+        }
+
+        before.push_back(line.to_string());
+        if before.len() > context {
+            before.pop_front();
+        }
+    })?;
+    Ok(matches)
+}
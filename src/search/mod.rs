@@ -1,13 +1,33 @@
-pub mod parse;
+mod content;
+mod content_index;
+mod hit;
+mod parse;
+mod plan;
 #[cfg(test)]
 mod test;
-pub mod token;
+mod token;
+pub(crate) mod trigram;
 
-use std::{collections::HashMap, fs, path::PathBuf};
+pub use content_index::{ContentIndex, ContentRules, ContentUpdate};
+pub use hit::{LineMatch, SearchHit};
+pub(crate) use plan::io_pool;
+pub use plan::IO_THREADS;
+
+use std::{
+    collections::HashMap,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
 
 use token::SearchParamsTokenizer;
 
-use crate::file::File;
+use crate::file::{
+    kinds::{extension, Kinds},
+    Entry, File,
+};
 
 #[derive(Debug, Clone, PartialEq)]
 pub enum SearchExprValue {
@@ -23,33 +43,84 @@ pub struct SearchExpr {
 
 impl SearchExpr {
     pub fn process(&self, s: &str) -> bool {
+        self.matches(&|value| s.contains(value))
+    }
+
+    /// Evaluates the expression with `pred` telling if a value matches
+    pub fn matches(&self, pred: &impl Fn(&str) -> bool) -> bool {
         self.inverted
             ^ match &self.expr {
-                SearchExprValue::Operation(op) => {
-                    let lhs = op.lhs.process(s);
-                    match op.operation {
-                        SearchOperator::And => {
-                            if !lhs {
-                                return false;
-                            } else {
-                                return op.rhs.process(s);
-                            }
-                        }
-
-                        SearchOperator::Or => {
-                            if lhs {
-                                return true;
-                            } else {
-                                return op.rhs.process(s);
-                            }
-                        }
-                    }
+                SearchExprValue::Operation(op) => match op.operation {
+                    SearchOperator::And => op.lhs.matches(pred) && op.rhs.matches(pred),
+                    SearchOperator::Or => op.lhs.matches(pred) || op.rhs.matches(pred),
+                },
+                SearchExprValue::Value(value) => pred(value),
+            }
+    }
+
+    /// Whether the expression always holds (`Some(true)`), never does (`Some(false)`)
+    /// or can do either (`None`), with `pred` answering the same for a value
+    fn reach(&self, pred: &impl Fn(&str) -> Option<bool>) -> Option<bool> {
+        let res = match &self.expr {
+            SearchExprValue::Operation(op) => {
+                let (lhs, rhs) = (op.lhs.reach(pred), op.rhs.reach(pred));
+                match op.operation {
+                    SearchOperator::And => match (lhs, rhs) {
+                        (Some(false), _) | (_, Some(false)) => Some(false),
+                        (Some(true), Some(true)) => Some(true),
+                        _ => None,
+                    },
+                    SearchOperator::Or => match (lhs, rhs) {
+                        (Some(true), _) | (_, Some(true)) => Some(true),
+                        (Some(false), Some(false)) => Some(false),
+                        _ => None,
+                    },
                 }
-                SearchExprValue::Value(value) => s.contains(value),
             }
+            SearchExprValue::Value(value) => pred(value),
+        };
+        res.map(|res| res ^ self.inverted)
+    }
+}
+
+/// Whether a path under the directory at `dir` contains `value`, see [`SearchExpr::reach`]
+fn contained_under(dir: &str, value: &str) -> Option<bool> {
+    if dir.contains(value) {
+        // Every path under `dir` starts with it
+        Some(true)
+    } else if is_anchored(value) && !value.starts_with(dir) {
+        Some(false)
+    } else {
+        None
+    }
+}
+
+/// Parses `3`, `..3`, `2..` or `1..3`, bounds included
+fn depth_range(value: &str) -> Option<RangeInclusive<usize>> {
+    let (start, end) = value.split_once("..").unwrap_or((value, value));
+    let bound = |s: &str, default| match s {
+        "" => Some(default),
+        s => s.parse().ok(),
+    };
+    Some(bound(start, 0)?..=bound(end, usize::MAX)?)
+}
+
+/// Whether a file deeper than `depth` can be in the range of `value`, see [`SearchExpr::reach`]
+fn depth_under(depth: usize, value: &str) -> Option<bool> {
+    match depth_range(value) {
+        Some(range) if *range.end() <= depth => Some(false),
+        Some(range) if *range.start() <= depth + 1 && *range.end() == usize::MAX => Some(true),
+        Some(_) => None,
+        None => Some(false),
     }
 }
 
+/// Values starting with a drive letter can only be found at the start of a path,
+/// `:` being invalid in file names
+fn is_anchored(value: &str) -> bool {
+    cfg!(windows) && value.chars().nth(1) == Some(':')
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct SearchOperation {
     pub operation: SearchOperator,
@@ -68,51 +139,189 @@ pub struct SearchParams {
     name: Option<SearchExpr>,
     path: Option<SearchExpr>,
     content: Option<SearchExpr>,
+    /// Matched against `yes` or `no`
+    ignored: Option<SearchExpr>,
+    /// Values are ranges, see [`depth_range`]
+    depth: Option<SearchExpr>,
+    /// Matched against the name of the parent directory
+    parent: Option<SearchExpr>,
+    /// Matches if the name of any ancestor does
+    ancestor: Option<SearchExpr>,
+    /// Matches directories where the name of any child does
+    child: Option<SearchExpr>,
+    /// Matched against the last extension of files, case insensitive
+    ext: Option<SearchExpr>,
+    /// Matched against the kinds listing the extension of files, see [`Kinds`]
+    kind: Option<SearchExpr>,
+    kinds: Kinds,
+    /// Larger files are not searched by `content`, `None` for no limit
+    max_content_size: Option<u64>,
 }
 
 impl From<HashMap<SearchField, SearchExpr>> for SearchParams {
-    fn from(mut value: HashMap<SearchField, SearchExpr>) -> Self {
+    fn from(value: HashMap<SearchField, SearchExpr>) -> Self {
+        let mut value = value
+            .into_iter()
+            .map(|(field, expr)| (field, expr.planned()))
+            .collect::<HashMap<_, _>>();
         Self {
             name: value.remove(&SearchField::Name),
             path: value.remove(&SearchField::Path),
             content: value.remove(&SearchField::Content),
+            ignored: value.remove(&SearchField::Ignored),
+            depth: value.remove(&SearchField::Depth),
+            parent: value.remove(&SearchField::Parent),
+            ancestor: value.remove(&SearchField::Ancestor),
+            child: value.remove(&SearchField::Child),
+            ext: value.remove(&SearchField::Ext),
+            kind: value.remove(&SearchField::Kind),
+            kinds: Kinds::default(),
+            max_content_size: Some(Self::DEFAULT_MAX_CONTENT_SIZE),
         }
     }
 }
 
+fn yes_no(b: bool) -> &'static str {
+    if b {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
 impl SearchParams {
-    pub fn process(&self, file: &(&File, PathBuf)) -> bool {
-        if let Some(name_expr) = &self.name {
-            if !name_expr.process(&file.0.name) {
-                return false;
-            }
+    pub const DEFAULT_MAX_CONTENT_SIZE: u64 = 64 << 20;
+
+    pub fn with_max_content_size(self, max_content_size: Option<u64>) -> Self {
+        Self {
+            max_content_size,
+            ..self
         }
+    }
 
-        if let Some(path_expr) = &self.path {
-            let Some(s) = file.1.to_str() else {
-                return false;
-            };
+    /// The kinds `kind<...>` matches, the built-in ones by default.
+    /// Searches through a [`FileIndex`](crate::FileIndex) use its own
+    pub fn with_kinds(self, kinds: Kinds) -> Self {
+        Self { kinds, ..self }
+    }
+
+    pub(crate) fn name(&self) -> Option<&SearchExpr> {
+        self.name.as_ref()
+    }
+
+    pub fn process(&self, entry: &Entry) -> bool {
+        self.matches_index(entry) && self.matches_content(entry.file, entry.path)
+    }
+
+    /// The fields checked from the index, in the order they are checked in, cheapest first
+    fn steps(&self) -> impl Iterator<Item = (SearchField, &SearchExpr)> {
+        [
+            (SearchField::Depth, &self.depth),
+            (SearchField::Ignored, &self.ignored),
+            (SearchField::Ext, &self.ext),
+            (SearchField::Kind, &self.kind),
+            (SearchField::Name, &self.name),
+            (SearchField::Parent, &self.parent),
+            (SearchField::Child, &self.child),
+            (SearchField::Ancestor, &self.ancestor),
+            (SearchField::Path, &self.path),
+        ]
+        .into_iter()
+        .filter_map(|(field, expr)| Some((field, expr.as_ref()?)))
+    }
 
-            if !path_expr.process(s) {
-                return false;
+    fn check(&self, field: SearchField, expr: &SearchExpr, entry: &Entry) -> bool {
+        let file = entry.file;
+        // Directories have no extension
+        let ext = || extension(&file.name).filter(|_| !file.is_dir());
+        match field {
+            SearchField::Depth => expr.matches(&|value| {
+                depth_range(value).is_some_and(|range| range.contains(&entry.depth))
+            }),
+            SearchField::Ignored => expr.process(yes_no(file.is_ignored())),
+            SearchField::Ext => expr.matches(&|value| {
+                ext().is_some_and(|ext| ext.eq_ignore_ascii_case(value.trim_start_matches('.')))
+            }),
+            SearchField::Kind => {
+                expr.matches(&|value| ext().is_some_and(|ext| self.kinds.contains(value, ext)))
             }
+            SearchField::Name => expr.process(&file.name),
+            SearchField::Parent => entry
+                .parents
+                .last()
+                .is_some_and(|parent| expr.process(&parent.name)),
+            SearchField::Child => file.children.iter().any(|child| expr.process(&child.name)),
+            SearchField::Ancestor => entry
+                .parents
+                .iter()
+                .any(|parent| expr.process(&parent.name)),
+            SearchField::Path => entry.path.to_str().is_some_and(|s| expr.process(s)),
+            SearchField::Content => true,
         }
+    }
+
+    /// Everything but `content`
+    pub fn matches_index(&self, entry: &Entry) -> bool {
+        self.steps()
+            .all(|(field, expr)| self.check(field, expr, entry))
+    }
 
-        if let Some(content_expr) = &self.content {
-            if file.0.children.is_empty() {
-                let Some(content) = fs::read_to_string(&file.1).ok() else {
-                    return false;
-                };
+    pub fn matches_content(&self, file: &File, path: &Path) -> bool {
+        self.content
+            .as_ref()
+            .is_none_or(|expr| content::matches(file, path, expr, self.max_content_size))
+    }
 
-                if !content_expr.process(&content) {
-                    return false;
-                }
+    /// Keeps the results matching `content`, from `index` for the files it holds up to date
+    /// and reading the others on the I/O threads, stops early when `cancel` is set
+    pub fn filter_content<'a>(
+        &self,
+        results: Vec<(&'a File, PathBuf)>,
+        index: Option<&ContentIndex>,
+        cancel: &AtomicBool,
+    ) -> Vec<(&'a File, PathBuf)> {
+        let Some(expr) = &self.content else {
+            return results;
+        };
+
+        let lookup = index.map(|index| index.lookup(expr));
+        plan::io_pool().install(|| {
+            results
+                .into_par_iter()
+                .filter(|(file, path)| {
+                    let indexed = (lookup.as_ref())
+                        .and_then(|lookup| lookup.matches(file, path, self.max_content_size));
+                    !cancel.load(Ordering::Relaxed)
+                        && indexed.unwrap_or_else(|| self.matches_content(file, path))
+                })
+                .collect()
+        })
+    }
+
+    /// Whether nothing under the directory of `entry` can match,
+    /// everything under an ignored directory being ignored
+    pub fn prunes(&self, entry: &Entry) -> bool {
+        if let Some(expr) = &self.ignored {
+            if entry.file.is_ignored() && !expr.process(yes_no(true)) {
+                return true;
+            }
+        }
+
+        if let Some(expr) = &self.depth {
+            if expr.reach(&|value| depth_under(entry.depth, value)) == Some(false) {
+                return true;
             }
         }
 
-        true
+        match (&self.path, entry.path.to_str()) {
+            (Some(expr), Some(dir)) => {
+                expr.reach(&|value| contained_under(dir, value)) == Some(false)
+            }
+            _ => false,
+        }
     }
 
+    #[allow(clippy::should_implement_trait)]
     pub fn from_str(s: &str) -> Self {
         SearchParams::parse(SearchParamsTokenizer::new(s).tokens())
     }
@@ -124,6 +333,13 @@ pub enum SearchField {
     Name,
     Path,
     Content,
+    Ignored,
+    Depth,
+    Parent,
+    Ancestor,
+    Child,
+    Ext,
+    Kind,
 }
 
 impl SearchField {
@@ -132,7 +348,29 @@ impl SearchField {
             "name" => Self::Name,
             "content" => Self::Content,
             "path" => Self::Path,
+            "ignored" => Self::Ignored,
+            "depth" => Self::Depth,
+            "parent" => Self::Parent,
+            "ancestor" => Self::Ancestor,
+            "child" => Self::Child,
+            "ext" => Self::Ext,
+            "kind" => Self::Kind,
             _ => return None,
         })
     }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Name => "name",
+            Self::Path => "path",
+            Self::Content => "content",
+            Self::Ignored => "ignored",
+            Self::Depth => "depth",
+            Self::Parent => "parent",
+            Self::Ancestor => "ancestor",
+            Self::Child => "child",
+            Self::Ext => "ext",
+            Self::Kind => "kind",
+        }
+    }
 }
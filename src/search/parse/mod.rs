@@ -11,7 +11,7 @@ use crate::search::{
 use super::SearchParams;
 
 impl SearchParams {
-    pub fn parse(tokens: Vec<SearchParamsToken>) -> SearchParams {
+    pub(crate) fn parse(tokens: Vec<SearchParamsToken>) -> SearchParams {
         let mut map = HashMap::new();
         let mut delimiter_stack = vec![(SearchField::Name, false, Vec::new())];
 
@@ -65,13 +65,18 @@ impl SearchParams {
                         }
                         Some(SearchParamsToken::Paren(Opening::Opened)) => {
                             let expr = parse_expr(iter);
-                            if let Some(SearchParamsToken::Paren(Opening::Closed)) = iter.next() {
-                                expr
-                            } else {
-                                panic!("Unmatched parenthesis");
+                            // An unclosed parenthesis is closed at the end of the scope
+                            if let Some(SearchParamsToken::Paren(Opening::Closed)) = iter.peek() {
+                                iter.next();
                             }
+                            expr
                         }
-                        _ => panic!("Unexpected token"),
+                        // Incomplete queries (e.g. while typing `foo |`) match everything
+                        // where the missing operand is, an empty value is always contained
+                        _ => SearchExpr {
+                            expr: SearchExprValue::Value(String::new()),
+                            inverted: false,
+                        },
                     };
 
                     while let Some(token) = iter.peek() {
@@ -7,7 +7,7 @@ use crate::search::{
 #[test]
 fn hard_search_parse() {
     let input = "some ?(word | ?and) other content?<this | ?that woaw>";
-    let search_params = SearchParams::from_str(&input);
+    let search_params = SearchParams::from_str(input);
 
     let mut map = HashMap::new();
     map.insert(
@@ -134,3 +134,32 @@ fn easy_search_parse() {
 
     pretty_assertions::assert_eq!(search_params, SearchParams::from(map));
 }
+
+#[test]
+fn incomplete_search_parse() {
+    let empty = SearchExpr {
+        expr: SearchExprValue::Value(String::new()),
+        inverted: false,
+    };
+
+    let mut map = HashMap::new();
+    map.insert(
+        SearchField::Name,
+        SearchExpr {
+            expr: SearchExprValue::Operation(Box::new(SearchOperation {
+                operation: SearchOperator::Or,
+                lhs: SearchExpr {
+                    expr: SearchExprValue::Value("some".to_string()),
+                    inverted: false,
+                },
+                rhs: empty.clone(),
+            })),
+            inverted: false,
+        },
+    );
+    assert_eq!(SearchParams::from_str("some |"), SearchParams::from(map));
+
+    let mut map = HashMap::new();
+    map.insert(SearchField::Name, empty);
+    assert_eq!(SearchParams::from_str("("), SearchParams::from(map));
+}
@@ -0,0 +1,187 @@
+use std::{
+    fmt::{self, Display, Write},
+    sync::OnceLock,
+};
+
+use rayon::{ThreadPool, ThreadPoolBuilder};
+
+use crate::output::human_size;
+
+use super::{
+    ContentIndex, SearchExpr, SearchExprValue, SearchOperation, SearchOperator, SearchParams,
+};
+
+/// Files read at the same time by content searches
+pub const IO_THREADS: usize = 8;
+
+/// Content is read there so blocking I/O doesn't hold rayon's CPU threads
+pub(crate) fn io_pool() -> &'static ThreadPool {
+    static POOL: OnceLock<ThreadPool> = OnceLock::new();
+    POOL.get_or_init(|| {
+        ThreadPoolBuilder::new()
+            .num_threads(IO_THREADS)
+            .thread_name(|i| format!("caver-io-{i}"))
+            .build()
+            .expect("failed to start the I/O threads")
+    })
+}
+
+impl SearchExpr {
+    /// Estimated share of strings matching, longer values being rarer
+    pub fn selectivity(&self) -> f64 {
+        let selectivity = match &self.expr {
+            SearchExprValue::Operation(op) => {
+                let (lhs, rhs) = (op.lhs.selectivity(), op.rhs.selectivity());
+                match op.operation {
+                    SearchOperator::And => lhs * rhs,
+                    SearchOperator::Or => 1.0 - (1.0 - lhs) * (1.0 - rhs),
+                }
+            }
+            SearchExprValue::Value(value) => 1.0 / (1 + value.chars().count()) as f64,
+        };
+
+        match self.inverted {
+            true => 1.0 - selectivity,
+            false => selectivity,
+        }
+    }
+
+    /// Pushes the operands of a chain of `operation` into `out`
+    fn flatten(self, operation: &SearchOperator, out: &mut Vec<SearchExpr>) {
+        match self.expr {
+            SearchExprValue::Operation(op) if !self.inverted && op.operation == *operation => {
+                let op = *op;
+                op.lhs.flatten(operation, out);
+                op.rhs.flatten(operation, out);
+            }
+            _ => out.push(self),
+        }
+    }
+
+    /// Reorders the operands so an and stops at the first that likely fails,
+    /// and an or at the first that likely matches
+    pub fn planned(self) -> SearchExpr {
+        let SearchExprValue::Operation(op) = &self.expr else {
+            return self;
+        };
+        let operation = op.operation.clone();
+        let inverted = self.inverted;
+
+        let mut operands = Vec::new();
+        SearchExpr {
+            inverted: false,
+            ..self
+        }
+        .flatten(&operation, &mut operands);
+
+        let mut operands = operands
+            .into_iter()
+            .map(|operand| {
+                let operand = operand.planned();
+                (operand.selectivity(), operand)
+            })
+            .collect::<Vec<_>>();
+        operands.sort_by(|a, b| match operation {
+            SearchOperator::And => a.0.total_cmp(&b.0),
+            SearchOperator::Or => b.0.total_cmp(&a.0),
+        });
+
+        let mut operands = operands.into_iter().map(|(_, operand)| operand);
+        let first = operands.next().unwrap();
+        let mut expr = operands.fold(first, |lhs, rhs| SearchExpr {
+            expr: SearchExprValue::Operation(Box::new(SearchOperation {
+                operation: operation.clone(),
+                lhs,
+                rhs,
+            })),
+            inverted: false,
+        });
+        expr.inverted = inverted;
+        expr
+    }
+}
+
+/// In the query syntax
+impl Display for SearchExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.inverted {
+            write!(f, "?")?;
+        }
+
+        match &self.expr {
+            SearchExprValue::Value(value) => write!(f, "{value}"),
+            SearchExprValue::Operation(op) => {
+                if self.inverted {
+                    write!(f, "(")?;
+                }
+
+                let separator = match op.operation {
+                    SearchOperator::And => " ",
+                    SearchOperator::Or => " | ",
+                };
+                for (i, operand) in [&op.lhs, &op.rhs].into_iter().enumerate() {
+                    if i == 1 {
+                        write!(f, "{separator}")?;
+                    }
+                    match &operand.expr {
+                        // An or inside an and needs parentheses, the reverse doesn't
+                        SearchExprValue::Operation(inner)
+                            if !operand.inverted
+                                && inner.operation == SearchOperator::Or
+                                && op.operation == SearchOperator::And =>
+                        {
+                            write!(f, "({operand})")?
+                        }
+                        _ => write!(f, "{operand}")?,
+                    }
+                }
+
+                if self.inverted {
+                    write!(f, ")")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl SearchParams {
+    /// The order the fields are checked in, one line each.
+    /// Every field but `content` is checked from the index, `content` looks the files left up
+    /// in `content` and reads the others
+    pub fn explain(&self, content: Option<&ContentIndex>) -> String {
+        let mut out = String::new();
+        let steps = self.steps().collect::<Vec<_>>();
+        if steps.is_empty() && self.content.is_none() {
+            out.push_str("everything matches\n");
+        }
+
+        for (i, (field, expr)) in steps.iter().enumerate() {
+            let _ = writeln!(
+                out,
+                "{}. {}<{expr}>  index, ~{:.0}% match",
+                i + 1,
+                field.name(),
+                expr.selectivity() * 100.0
+            );
+        }
+
+        if let Some(expr) = &self.content {
+            let limit = match self.max_content_size {
+                Some(size) => format!("files up to {}", human_size(size)),
+                None => "any size".to_string(),
+            };
+            let indexed = match content {
+                Some(index) => format!("content index for its {} files, otherwise ", index.len()),
+                None => String::new(),
+            };
+            let _ = writeln!(
+                out,
+                "{}. content<{expr}>  {indexed}reads the files left on {IO_THREADS} I/O threads, {limit}",
+                steps.len() + 1,
+            );
+        }
+
+        out
+    }
+}
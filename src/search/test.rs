@@ -1,6 +1,10 @@
-use std::time::Instant;
+use std::{fs, ops::Range, path::Path, time::Instant};
 
-use crate::file::index::FileIndex;
+use crate::{
+    file::{index::FileIndex, Entry, File},
+    search::{ContentIndex, ContentRules, ContentUpdate, SearchParams},
+    test_util::{names, sorted_names, temp_dir},
+};
 
 #[test]
 pub fn find_main_rs() {
@@ -19,3 +23,196 @@ pub fn find_main_rs() {
         .find(|(_, path)| path.to_string_lossy().to_string().contains("caver"))
         .is_some())
 }
+
+#[test]
+fn content_encodings() {
+    let root = temp_dir("content");
+    fs::create_dir_all(root.join("empty")).unwrap();
+    let utf16 = |to_bytes: fn(u16) -> [u8; 2], bom: [u8; 2]| {
+        let units = "say hello".encode_utf16().flat_map(to_bytes);
+        bom.into_iter().chain(units).collect::<Vec<_>>()
+    };
+    fs::write(root.join("plain.txt"), "say hello").unwrap();
+    fs::write(root.join("le.txt"), utf16(u16::to_le_bytes, [0xFF, 0xFE])).unwrap();
+    fs::write(root.join("be.txt"), utf16(u16::to_be_bytes, [0xFE, 0xFF])).unwrap();
+    fs::write(root.join("latin1.txt"), b"caf\xe9 hello").unwrap();
+    fs::write(root.join("binary.bin"), b"hello\0\x01").unwrap();
+    // Only the first chunk tells binary files apart
+    fs::write(
+        root.join("late_nul.txt"),
+        "hello".to_string() + &"a".repeat(1 << 16) + "\0",
+    )
+    .unwrap();
+    // Cut in two by the end of the first chunk
+    fs::write(root.join("cut.txt"), "a".repeat((1 << 16) - 2) + "hello").unwrap();
+
+    let fi = FileIndex::walk(&[&root]).unwrap();
+    assert_eq!(
+        names(&fi, "content<hello>"),
+        [
+            "be.txt",
+            "cut.txt",
+            "late_nul.txt",
+            "latin1.txt",
+            "le.txt",
+            "plain.txt"
+        ]
+    );
+    assert_eq!(
+        sorted_names(fi.search(
+            SearchParams::from_str("content<hello ?caf>").with_max_content_size(Some(100))
+        )),
+        ["be.txt", "le.txt", "plain.txt"]
+    );
+}
+
+#[test]
+fn query_plan() {
+    let params = SearchParams::from_str("a | abcd abc path<x> content<y> depth<2>");
+    assert_eq!(
+        params
+            .explain(None)
+            .lines()
+            .map(|line| line.split("  ").next().unwrap())
+            .collect::<Vec<_>>(),
+        [
+            "1. depth<2>",
+            "2. name<a | abcd abc>",
+            "3. path<x>",
+            "4. content<y>"
+        ]
+    );
+
+    // Planning doesn't change the results
+    let planned = SearchParams::from_str("(d | ?e ) xyz ab ?( q | c )");
+    assert_eq!(
+        planned
+            .explain(None)
+            .lines()
+            .next()
+            .unwrap()
+            .split("  ")
+            .next(),
+        Some("1. name<xyz ?(q | c) ab (d | ?e)>")
+    );
+    let name = |name: &str| {
+        let file = File {
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let entry = Entry {
+            file: &file,
+            depth: 1,
+            path: Path::new(name),
+            parents: &[],
+        };
+        planned.process(&entry)
+    };
+    assert!(name("xyz ab d"));
+    assert!(name("xyz ab"));
+    assert!(!name("xyz ab e"));
+    assert!(!name("xyz ab c"));
+    assert!(!name("xyz d"));
+}
+
+#[test]
+fn content_index() {
+    let root = temp_dir("content-index");
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "fn main() {}").unwrap();
+    fs::write(root.join("sub/b.rs"), "let mainly = 1;").unwrap();
+    fs::write(root.join("sub/short.txt"), "ab").unwrap();
+    fs::write(root.join("empty.txt"), "").unwrap();
+    fs::write(root.join("binary.bin"), b"main\0").unwrap();
+
+    let rules = ContentRules {
+        paths: vec![root.to_string_lossy().into_owned()],
+        extensions: vec![],
+        ..Default::default()
+    };
+    let mut fi = FileIndex::walk(&[&root]).unwrap();
+    let mut content = ContentIndex::new();
+    assert_eq!(
+        content.update(&fi, &rules),
+        ContentUpdate {
+            read: 5,
+            removed: 0,
+            unchanged: 0
+        }
+    );
+
+    let queries = [
+        "main",
+        "main()",
+        "mainly | ab",
+        "?main",
+        "a",
+        "b ?ly",
+        "xyz",
+        "in(",
+    ];
+    let search = |fi: &FileIndex, query| names(fi, &format!("content<{query}>"));
+    let read = queries.map(|query| search(&fi, query));
+    fi.content = Some(content);
+    assert_eq!(queries.map(|query| search(&fi, query)), read);
+
+    // Answered without reading the files
+    fs::remove_file(root.join("a.txt")).unwrap();
+    assert_eq!(search(&fi, "main()"), ["a.txt"]);
+
+    fs::write(root.join("sub/b.rs"), "let main = 2;").unwrap();
+    let mut content = fi.content.take().unwrap();
+    let fi = FileIndex::walk(&[&root]).unwrap();
+    assert_eq!(
+        content.update(&fi, &rules),
+        ContentUpdate {
+            read: 1,
+            removed: 1,
+            unchanged: 3
+        }
+    );
+    assert_eq!(content.len(), 4);
+}
+
+#[test]
+fn search_hit() {
+    let root = temp_dir("hit");
+    let path = root.join("main.rs");
+    fs::write(&path, "one\nfn main() {\n    main();\n}\nlast\n").unwrap();
+
+    let params = SearchParams::from_str("ma | in ?rs path<caver-hit> content<main ?xyz>");
+    let hit = params.hit("main.rs", &path, Some(1));
+    let pairs = |ranges: &[Range<usize>]| {
+        (ranges.iter())
+            .map(|range| (range.start, range.end))
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(pairs(&hit.name), [(0, 4)]);
+
+    let path_str = path.to_string_lossy();
+    let dir = path_str.find("caver-hit").unwrap();
+    let name = path_str.len() - "main.rs".len();
+    assert_eq!(pairs(&hit.path), [(dir, dir + 9), (name, name + 4)]);
+
+    let lines = hit
+        .lines
+        .iter()
+        .map(|line| (line.line, line.column, pairs(&line.ranges)))
+        .collect::<Vec<_>>();
+    assert_eq!(lines, [(2, 4, vec![(3, 7)]), (3, 5, vec![(4, 8)])]);
+    assert_eq!(hit.lines[0].before, ["one"]);
+    assert_eq!(hit.lines[0].after, ["    main();"]);
+    assert_eq!(hit.lines[1].before, ["fn main() {"]);
+    assert_eq!(hit.lines[1].after, ["}"]);
+
+    assert!(params.hit("main.rs", &path, None).lines.is_empty());
+
+    // A line cut in two by the end of the first chunk
+    let long = "a".repeat((1 << 16) - 4) + "main";
+    fs::write(&path, format!("one\n{long}\nlast")).unwrap();
+    let hit = params.hit("main.rs", &path, Some(1));
+    assert_eq!(hit.lines.len(), 1);
+    assert_eq!((hit.lines[0].line, hit.lines[0].column), (2, (1 << 16) - 3));
+    assert_eq!(hit.lines[0].text, long);
+    assert_eq!(hit.lines[0].after, ["last"]);
+}
@@ -22,6 +22,11 @@ pub enum Opening {
     Closed,
 }
 
+/// Separators and drive letters are allowed for paths, e.g. `path<C:\Users>`
+fn is_word_char(char: char) -> bool {
+    char.is_valid_windows_file_name() || matches!(char, '\\' | '/' | ':')
+}
+
 impl<'a> SearchParamsTokenizer<'a> {
     pub fn new(s: &'a str) -> Self {
         Self {
@@ -60,10 +65,10 @@ impl<'a> SearchParamsTokenizer<'a> {
                     self.iter.next();
                     tokens.push(SearchParamsToken::Inverter);
                 }
-                _ if char.is_valid_windows_file_name() => {
+                _ if is_word_char(char) => {
                     let mut word = String::new();
                     while let Some(&char) = self.iter.peek() {
-                        if char.is_valid_windows_file_name() && !char.is_whitespace() {
+                        if is_word_char(char) && !char.is_whitespace() {
                             word.push(char);
                             self.iter.next();
                         } else {
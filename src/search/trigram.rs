@@ -0,0 +1,21 @@
+/// Bits of a character in a trigram, enough for any `char`
+pub(crate) const CHAR_BITS: u32 = 21;
+
+/// The three characters packed so trigrams sharing a prefix are next to each other
+pub(crate) fn trigram(chars: [char; 3]) -> u64 {
+    chars
+        .into_iter()
+        .fold(0, |key, c| (key << CHAR_BITS) | c as u64)
+}
+
+/// The trigrams of `s`, once each
+pub(crate) fn trigrams(s: &str) -> Vec<u64> {
+    let chars = s.chars().collect::<Vec<_>>();
+    let mut trigrams = chars
+        .windows(3)
+        .map(|window| trigram([window[0], window[1], window[2]]))
+        .collect::<Vec<_>>();
+    trigrams.sort_unstable();
+    trigrams.dedup();
+    trigrams
+}
@@ -0,0 +1,75 @@
+//! Helpers shared by the tests of several modules
+
+use std::{
+    fs,
+    ops::Deref,
+    path::{Path, PathBuf},
+    process,
+};
+
+use crate::{file::index::FileIndex, File};
+
+/// A directory in the temp directory, removed with everything under it when dropped
+pub struct TempDir(PathBuf);
+
+impl Deref for TempDir {
+    type Target = Path;
+
+    fn deref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl AsRef<Path> for TempDir {
+    fn as_ref(&self) -> &Path {
+        &self.0
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+/// An empty directory in the temp directory, `name` keeps the tests apart
+pub fn temp_dir(name: &str) -> TempDir {
+    let root = std::env::temp_dir().join(format!("caver-{name}-{}", process::id()));
+    fs::create_dir_all(&root).unwrap();
+    TempDir(root)
+}
+
+/// A [`temp_dir`] holding `a.txt` and `sub/b.txt`
+pub fn temp_tree(name: &str) -> TempDir {
+    let root = temp_dir(name);
+    fs::create_dir_all(root.join("sub")).unwrap();
+    fs::write(root.join("a.txt"), "hello").unwrap();
+    fs::write(root.join("sub").join("b.txt"), "hi").unwrap();
+    root
+}
+
+/// The names of `results`, sorted
+pub fn sorted_names(results: Vec<(&File, PathBuf)>) -> Vec<String> {
+    let mut names = (results.into_iter())
+        .map(|(file, _)| file.name.clone())
+        .collect::<Vec<_>>();
+    names.sort();
+    names
+}
+
+/// The names of the files of `fi` matching `query`, sorted
+pub fn names(fi: &FileIndex, query: &str) -> Vec<String> {
+    sorted_names(fi.search_str(query))
+}
+
+/// The paths under `root` of the files of `fi` matching `query`, with `/` separators, sorted
+pub fn relative_paths(fi: &FileIndex, root: &Path, query: &str) -> Vec<String> {
+    let mut paths = (fi.search_str(query).into_iter())
+        .map(|(_, path)| {
+            let path = path.strip_prefix(root).unwrap();
+            path.to_string_lossy().replace('\\', "/")
+        })
+        .collect::<Vec<_>>();
+    paths.sort();
+    paths
+}
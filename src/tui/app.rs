@@ -0,0 +1,230 @@
+use std::path::{Path, PathBuf};
+
+use caver::{
+    file::{
+        usage::{DirUsage, Usage},
+        File,
+    },
+    keymap::{Action, ChordMatcher, Key, KeyCode, KeyMatch, Keymap},
+    search::SearchField,
+};
+
+/// What the event loop has to do after a key press
+#[derive(Debug, PartialEq)]
+pub enum Effect {
+    None,
+    Search,
+    Open(PathBuf),
+    Reveal(PathBuf),
+    Copy(String),
+    /// Show the largest directories under this one, or the disks
+    DiskUsage(Option<PathBuf>),
+    Rebuild,
+    Quit,
+}
+
+const PAGE: isize = 10;
+
+#[derive(Default)]
+pub struct App<'a> {
+    pub query: String,
+    /// Field the typed query applies to
+    pub field: SearchField,
+    /// Added by [`Action::RefineQuery`], kept apart from the typed query so the field doesn't wrap them
+    pub refinements: Vec<String>,
+    pub results: Vec<(&'a File, PathBuf)>,
+    /// What is under each result while the disk usage is shown
+    pub usage: Vec<Usage>,
+    pub selected: usize,
+    pub searching: bool,
+    /// Shown in the status line until the next key press
+    pub message: Option<String>,
+    /// Progress of the index rebuild running in the background
+    pub rebuilding: Option<String>,
+    pub keymap: Keymap,
+    pub matcher: ChordMatcher,
+}
+
+impl<'a> App<'a> {
+    pub fn new(keymap: Keymap) -> Self {
+        Self {
+            keymap,
+            ..Default::default()
+        }
+    }
+
+    pub fn selection(&self) -> Option<&(&'a File, PathBuf)> {
+        self.results.get(self.selected)
+    }
+
+    /// The query as given to the search, the typed part wrapped in the selected field
+    pub fn search_query(&self) -> String {
+        let typed = (!self.query.trim().is_empty()).then(|| match self.field {
+            SearchField::Name => self.query.clone(),
+            field => format!("{}<{}>", field.name(), self.query),
+        });
+        (typed.into_iter())
+            .chain(self.refinements.iter().cloned())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// Nothing typed nor refined, there is nothing to search
+    pub fn is_empty(&self) -> bool {
+        self.query.trim().is_empty() && self.refinements.is_empty()
+    }
+
+    pub fn set_results(&mut self, results: Vec<(&'a File, PathBuf)>) {
+        self.results = results;
+        self.usage.clear();
+        self.selected = 0;
+        self.searching = false;
+    }
+
+    /// Replaces the results with directories, the largest first
+    pub fn set_usage(&mut self, dirs: Vec<DirUsage<'a>>) {
+        let (results, usage) = dirs
+            .into_iter()
+            .map(|dir| ((dir.file, dir.path), dir.usage))
+            .unzip();
+        self.set_results(results);
+        self.usage = usage;
+    }
+
+    /// The selected directory, or the one containing the selected file
+    fn selected_dir(&self) -> Option<&Path> {
+        let (file, path) = self.selection()?;
+        Some(if file.is_dir() {
+            path.as_path()
+        } else {
+            path.parent().unwrap_or(Path::new(""))
+        })
+    }
+
+    fn select(&mut self, offset: isize) {
+        if self.results.is_empty() {
+            return;
+        }
+
+        self.selected = self
+            .selected
+            .saturating_add_signed(offset)
+            .min(self.results.len() - 1);
+    }
+
+    /// Restricts the query to the selected directory, or to the one containing the selected file
+    fn refine(&mut self) -> Effect {
+        let Some(dir) = self.selected_dir() else {
+            return Effect::None;
+        };
+
+        let refinement = format!("path<{}>", dir.to_string_lossy());
+        self.refinements.push(refinement);
+        Effect::Search
+    }
+
+    pub fn apply(&mut self, action: Action) -> Effect {
+        match action {
+            Action::Open => self
+                .selection()
+                .map_or(Effect::None, |(_, path)| Effect::Open(path.clone())),
+            Action::RevealInFolder => self
+                .selection()
+                .map_or(Effect::None, |(_, path)| Effect::Reveal(path.clone())),
+            Action::CopyPath => self.selection().map_or(Effect::None, |(_, path)| {
+                Effect::Copy(path.to_string_lossy().into_owned())
+            }),
+            Action::CopyName => self
+                .selection()
+                .map_or(Effect::None, |(file, _)| Effect::Copy(file.name.clone())),
+            Action::NextResult => {
+                self.select(1);
+                Effect::None
+            }
+            Action::PreviousResult => {
+                self.select(-1);
+                Effect::None
+            }
+            Action::NextPage => {
+                self.select(PAGE);
+                Effect::None
+            }
+            Action::PreviousPage => {
+                self.select(-PAGE);
+                Effect::None
+            }
+            Action::FirstResult => {
+                self.selected = 0;
+                Effect::None
+            }
+            Action::LastResult => {
+                self.selected = self.results.len().saturating_sub(1);
+                Effect::None
+            }
+            Action::RefineQuery => self.refine(),
+            Action::ToggleFieldFilter => {
+                self.field = match self.field {
+                    SearchField::Name => SearchField::Path,
+                    SearchField::Path => SearchField::Content,
+                    _ => SearchField::Name,
+                };
+                Effect::Search
+            }
+            Action::ClearQuery => {
+                self.query.clear();
+                self.refinements.clear();
+                Effect::Search
+            }
+            Action::DiskUsage => Effect::DiskUsage(self.selected_dir().map(Path::to_path_buf)),
+            Action::RebuildIndex if self.rebuilding.is_some() => Effect::None,
+            Action::RebuildIndex => Effect::Rebuild,
+            Action::Quit => Effect::Quit,
+        }
+    }
+
+    pub fn on_key(&mut self, key: Key) -> Effect {
+        self.message = None;
+
+        match self.matcher.feed(&self.keymap, key) {
+            KeyMatch::Action(action) => self.apply(action),
+            KeyMatch::Pending => Effect::None,
+            // Keys that aren't bound to anything edit the query
+            KeyMatch::Unbound(keys) => match keys.as_slice() {
+                [Key {
+                    code: KeyCode::Char(c),
+                    modifiers,
+                }] if !modifiers.ctrl && !modifiers.alt => {
+                    self.query.push(*c);
+                    Effect::Search
+                }
+                // Then the refinements, once the typed query is gone
+                [Key {
+                    code: KeyCode::Backspace,
+                    ..
+                }] if self.query.pop().is_some() || self.refinements.pop().is_some() => {
+                    Effect::Search
+                }
+                _ => Effect::None,
+            },
+        }
+    }
+
+    /// Short help for the status line, built from the current bindings
+    pub fn hints(&self) -> String {
+        [
+            (Action::Open, "open"),
+            (Action::RevealInFolder, "reveal"),
+            (Action::CopyPath, "copy path"),
+            (Action::RefineQuery, "refine"),
+            (Action::ToggleFieldFilter, "field"),
+            (Action::Quit, "quit"),
+        ]
+        .iter()
+        .filter_map(|(action, label)| {
+            let chord = self.keymap.bindings().get(action)?.first()?;
+            Some(format!("{chord} {label}"))
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+    }
+}
@@ -0,0 +1,279 @@
+pub mod app;
+#[cfg(test)]
+mod test;
+pub mod ui;
+
+use std::{
+    io::{self, Write},
+    mem,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use ratatui::{
+    backend::Backend,
+    crossterm::event::{self, Event, KeyEvent, KeyEventKind, KeyModifiers},
+    Terminal,
+};
+
+use caver::{
+    file::{index::FileIndex, usage::DirUsage, File},
+    keymap::{Key, KeyCode, Keymap, Modifiers},
+    progress::{Progress, ProgressFn},
+    search::SearchParams,
+    CaverResult,
+};
+
+use app::{App, Effect};
+
+fn spawn_detached(command: &mut Command) -> io::Result<()> {
+    command
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
+pub fn open(path: &Path) -> io::Result<()> {
+    if cfg!(windows) {
+        // Not `cmd /C start`, cmd would run what follows a `&` in the name
+        spawn_detached(Command::new("explorer").arg(path))
+    } else if cfg!(target_os = "macos") {
+        spawn_detached(Command::new("open").arg(path))
+    } else {
+        spawn_detached(Command::new("xdg-open").arg(path))
+    }
+}
+
+pub fn reveal(path: &Path) -> io::Result<()> {
+    if cfg!(windows) {
+        let mut arg = std::ffi::OsString::from("/select,");
+        arg.push(path);
+        spawn_detached(Command::new("explorer").arg(arg))
+    } else if cfg!(target_os = "macos") {
+        spawn_detached(Command::new("open").arg("-R").arg(path))
+    } else {
+        open(path.parent().unwrap_or(path))
+    }
+}
+
+/// Copies through the terminal with an OSC 52 sequence, which also works over ssh
+pub fn copy(text: &str) -> io::Result<()> {
+    let mut stdout = io::stdout();
+    write!(stdout, "\x1b]52;c;{}\x07", STANDARD.encode(text))?;
+    stdout.flush()
+}
+
+pub fn key_from_event(event: KeyEvent) -> Option<Key> {
+    use ratatui::crossterm::event::KeyCode as Code;
+
+    let code = match event.code {
+        Code::Char(c) => KeyCode::Char(c),
+        Code::F(n) => KeyCode::F(n),
+        Code::Enter => KeyCode::Enter,
+        Code::Esc => KeyCode::Esc,
+        Code::Tab => KeyCode::Tab,
+        Code::BackTab => KeyCode::BackTab,
+        Code::Backspace => KeyCode::Backspace,
+        Code::Delete => KeyCode::Delete,
+        Code::Insert => KeyCode::Insert,
+        Code::Up => KeyCode::Up,
+        Code::Down => KeyCode::Down,
+        Code::Left => KeyCode::Left,
+        Code::Right => KeyCode::Right,
+        Code::Home => KeyCode::Home,
+        Code::End => KeyCode::End,
+        Code::PageUp => KeyCode::PageUp,
+        Code::PageDown => KeyCode::PageDown,
+        _ => return None,
+    };
+
+    Some(Key::new(
+        code,
+        Modifiers {
+            ctrl: event.modifiers.contains(KeyModifiers::CONTROL),
+            alt: event.modifiers.contains(KeyModifiers::ALT),
+            shift: event.modifiers.contains(KeyModifiers::SHIFT),
+        },
+    ))
+}
+
+/// Why the interface stopped
+pub enum Exit {
+    Quit,
+    /// The index was rebuilt, the interface can be started again on it with the same query
+    Rebuilt {
+        index: Box<FileIndex>,
+        query: String,
+    },
+}
+
+type SearchResults<'a> = (u64, Vec<(&'a File, PathBuf)>);
+
+type UsageResults<'a> = (u64, CaverResult<Vec<DirUsage<'a>>>);
+
+enum RebuildEvent {
+    Progress(String),
+    Done(CaverResult<Box<FileIndex>>),
+}
+
+/// Runs the interface until the user quits, reading key presses from the terminal.
+/// `rebuild` is run on a background thread when the user asks for a new index
+pub fn run<B, R>(
+    terminal: &mut Terminal<B>,
+    index: &FileIndex,
+    keymap: Keymap,
+    query: String,
+    rebuild: R,
+) -> io::Result<Exit>
+where
+    B: Backend,
+    R: Fn(ProgressFn) -> CaverResult<FileIndex> + Clone + Send + 'static,
+{
+    let mut app = App::new(keymap);
+    app.query = query;
+    let (sender, receiver) = mpsc::channel::<SearchResults>();
+    let (usage_sender, usage_receiver) = mpsc::channel::<UsageResults>();
+    let (rebuild_sender, rebuild_receiver) = mpsc::channel::<RebuildEvent>();
+
+    thread::scope(|scope| {
+        let mut cancel = Arc::new(AtomicBool::new(false));
+        let mut generation = 0;
+        let mut effect = if app.query.is_empty() {
+            Effect::None
+        } else {
+            Effect::Search
+        };
+
+        let result = 'run: loop {
+            terminal.draw(|frame| ui::draw(frame, &app))?;
+
+            // Results of a search started before the latest keystroke are dropped
+            while let Ok((search_generation, results)) = receiver.try_recv() {
+                if search_generation == generation {
+                    app.set_results(results);
+                }
+            }
+
+            while let Ok((usage_generation, dirs)) = usage_receiver.try_recv() {
+                if usage_generation != generation {
+                    continue;
+                }
+                match dirs {
+                    Ok(dirs) => app.set_usage(dirs),
+                    Err(e) => {
+                        app.searching = false;
+                        app.message = Some(e.to_string());
+                    }
+                }
+            }
+
+            while let Ok(event) = rebuild_receiver.try_recv() {
+                match event {
+                    RebuildEvent::Progress(progress) => app.rebuilding = Some(progress),
+                    // The field and refinements are spelled out, the query means the same as a name query
+                    RebuildEvent::Done(Ok(index)) => {
+                        break 'run Ok(Exit::Rebuilt {
+                            index,
+                            query: app.search_query(),
+                        })
+                    }
+                    RebuildEvent::Done(Err(e)) => {
+                        app.rebuilding = None;
+                        app.message = Some(format!("rebuild failed : {e}"));
+                    }
+                }
+            }
+
+            let effect_result = match mem::replace(&mut effect, Effect::None) {
+                Effect::None => Ok(()),
+                Effect::Quit => break Ok(Exit::Quit),
+                Effect::Rebuild => {
+                    app.rebuilding = Some(String::new());
+
+                    // Not scoped, quitting must not wait for the rebuild to end.
+                    // The index is saved at once, quitting while it is written keeps the old one
+                    let rebuild = rebuild.clone();
+                    let sender = rebuild_sender.clone();
+                    thread::spawn(move || {
+                        let progress = |progress: Progress| {
+                            let _ = sender.send(RebuildEvent::Progress(progress.to_string()));
+                        };
+                        let _ = sender.send(RebuildEvent::Done(rebuild(&progress).map(Box::new)));
+                    });
+                    Ok(())
+                }
+                Effect::Search => {
+                    cancel.store(true, Ordering::Relaxed);
+                    cancel = Arc::new(AtomicBool::new(false));
+                    generation += 1;
+
+                    if app.is_empty() {
+                        app.set_results(vec![]);
+                    } else {
+                        app.searching = true;
+
+                        let params = SearchParams::from_str(&app.search_query());
+                        let cancel = cancel.clone();
+                        let sender = sender.clone();
+                        scope.spawn(move || {
+                            if let Some(results) = index.search_cancellable(params, &cancel) {
+                                let _ = sender.send((generation, results));
+                            }
+                        });
+                    }
+                    Ok(())
+                }
+                Effect::DiskUsage(path) => {
+                    cancel.store(true, Ordering::Relaxed);
+                    generation += 1;
+                    app.searching = true;
+
+                    // Sums everything under `path`, off the thread drawing the interface
+                    let sender = usage_sender.clone();
+                    scope.spawn(move || {
+                        // The disks, or the directories directly under `path`
+                        let depth = path.is_some() as usize;
+                        let dirs = index.du(path.as_deref(), depth).map(|dirs| {
+                            let dirs = dirs.into_iter().filter(|dir| dir.depth == depth);
+                            dirs.collect()
+                        });
+                        let _ = sender.send((generation, dirs));
+                    });
+                    Ok(())
+                }
+                Effect::Open(path) => open(&path),
+                Effect::Reveal(path) => reveal(&path),
+                Effect::Copy(text) => copy(&text),
+            };
+
+            if let Err(e) = effect_result {
+                app.message = Some(e.to_string());
+            }
+
+            if !event::poll(Duration::from_millis(30))? {
+                continue;
+            }
+            let Event::Key(event) = event::read()? else {
+                continue;
+            };
+            if event.kind != KeyEventKind::Press {
+                continue;
+            }
+            if let Some(key) = key_from_event(event) {
+                effect = app.on_key(key);
+            }
+        };
+
+        cancel.store(true, Ordering::Relaxed);
+        result
+    })
+}
@@ -0,0 +1,187 @@
+use std::path::PathBuf;
+
+use ratatui::{backend::TestBackend, Terminal};
+
+use caver::{
+    file::{index::FileIndex, File, FileKind},
+    keymap::{Key, KeyCode, Keymap, Modifiers},
+    search::{SearchField, SearchParams},
+};
+
+use super::{
+    app::{App, Effect},
+    ui,
+};
+
+fn index() -> FileIndex {
+    let file = |name: &str, size| File {
+        name: name.to_string(),
+        size,
+        ..Default::default()
+    };
+
+    FileIndex::new(vec![File {
+        name: "root".to_string(),
+        kind: FileKind::Directory,
+        children: vec![File {
+            name: "src".to_string(),
+            kind: FileKind::Directory,
+            children: vec![file("main.rs", 2048), file("lib.rs", 10)],
+            ..Default::default()
+        }],
+        ..Default::default()
+    }])
+}
+
+fn key(s: &str) -> Key {
+    Key::parse(s).unwrap()
+}
+
+fn type_query<'a>(app: &mut App<'a>, index: &'a FileIndex, query: &str) {
+    for c in query.chars() {
+        let key = Key::new(KeyCode::Char(c), Modifiers::default());
+        assert_eq!(app.on_key(key), Effect::Search);
+    }
+    app.set_results(index.search(SearchParams::from_str(&app.search_query())));
+}
+
+#[test]
+fn search_as_you_type() {
+    let index = index();
+    let mut app = App::default();
+
+    type_query(&mut app, &index, ".rs");
+    assert_eq!(app.results.len(), 2);
+
+    assert_eq!(app.on_key(key("down")), Effect::None);
+    assert_eq!(app.on_key(key("down")), Effect::None);
+    assert_eq!(app.selected, 1);
+
+    assert_eq!(
+        app.on_key(key("enter")),
+        Effect::Open(PathBuf::from("root/src/lib.rs"))
+    );
+    assert_eq!(
+        app.on_key(key("ctrl+y")),
+        Effect::Copy("root/src/lib.rs".to_string())
+    );
+    assert_eq!(app.on_key(key("alt+y")), Effect::Copy("lib.rs".to_string()));
+
+    assert_eq!(app.on_key(key("tab")), Effect::Search);
+    assert_eq!(app.query, ".rs");
+    assert_eq!(app.search_query(), ".rs path<root/src>");
+
+    assert_eq!(app.on_key(key("esc")), Effect::Quit);
+}
+
+#[test]
+fn keymap_actions() {
+    let index = index();
+    let mut app = App::new(Keymap::default());
+
+    assert_eq!(app.on_key(key("ctrl+f")), Effect::Search);
+    assert_eq!(app.field, SearchField::Path);
+    type_query(&mut app, &index, "src");
+    assert_eq!(app.search_query(), "path<src>");
+    assert_eq!(app.results.len(), 3);
+
+    assert_eq!(app.on_key(key("ctrl+r")), Effect::None);
+    assert_eq!(app.matcher.pending(), &[key("ctrl+r")]);
+    assert_eq!(app.on_key(key("ctrl+r")), Effect::Rebuild);
+
+    // A broken chord doesn't type anything
+    assert_eq!(app.on_key(key("ctrl+r")), Effect::None);
+    assert_eq!(app.on_key(key("x")), Effect::None);
+    assert_eq!(app.query, "src");
+
+    // Refinements stay out of the field
+    assert_eq!(app.on_key(key("tab")), Effect::Search);
+    assert_eq!(app.search_query(), "path<src> path<root/src>");
+    assert_eq!(app.on_key(key("backspace")), Effect::Search);
+    assert_eq!(app.query, "sr");
+}
+
+#[test]
+fn cancelled_search() {
+    let index = index();
+    let cancel = std::sync::atomic::AtomicBool::new(true);
+
+    assert!(index
+        .search_cancellable(SearchParams::from_str("main"), &cancel)
+        .is_none());
+}
+
+#[test]
+fn draw_results_and_details() {
+    let index = index();
+    let mut app = App::default();
+    type_query(&mut app, &index, "main");
+
+    let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+    terminal.draw(|frame| ui::draw(frame, &app)).unwrap();
+
+    let screen = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect::<String>();
+
+    assert!(screen.contains("root/src/main.rs"));
+    assert!(screen.contains("Size     : 2.0 KiB"));
+    assert!(screen.contains("1 results"));
+}
+
+#[test]
+fn rebuild_progress() {
+    let mut app = App::new(Keymap::default());
+    app.rebuilding = Some("/ : walking 16384".to_string());
+
+    // Already rebuilding
+    app.on_key(key("ctrl+r"));
+    assert_eq!(app.on_key(key("ctrl+r")), Effect::None);
+
+    let mut terminal = Terminal::new(TestBackend::new(100, 12)).unwrap();
+    terminal.draw(|frame| ui::draw(frame, &app)).unwrap();
+
+    let screen = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect::<String>();
+    assert!(screen.contains("rebuilding the index ... / : walking 16384"));
+}
+
+#[test]
+fn disk_usage() {
+    let index = index();
+    let mut app = App::new(Keymap::default());
+    type_query(&mut app, &index, "src");
+
+    assert_eq!(
+        app.on_key(key("ctrl+d")),
+        Effect::DiskUsage(Some(PathBuf::from("root/src")))
+    );
+    app.set_usage(index.du(None, 0).unwrap());
+    assert_eq!(app.results[0].1, PathBuf::from("root"));
+
+    let mut terminal = Terminal::new(TestBackend::new(100, 16)).unwrap();
+    terminal.draw(|frame| ui::draw(frame, &app)).unwrap();
+
+    let screen = terminal
+        .backend()
+        .buffer()
+        .content()
+        .iter()
+        .map(|cell| cell.symbol())
+        .collect::<String>();
+    assert!(screen.contains("2.0 KiB  root"));
+    assert!(screen.contains("Total    : 2.0 KiB, 2 files, 1 dirs"));
+
+    // Searching again leaves the disk usage
+    type_query(&mut app, &index, "s");
+    assert!(app.usage.is_empty());
+}
@@ -0,0 +1,118 @@
+use ratatui::{
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, List, ListItem, Paragraph, Wrap},
+    Frame,
+};
+use time::{format_description::well_known::Rfc3339, OffsetDateTime};
+
+use caver::output::human_size;
+
+use super::app::App;
+
+fn format_timestamp(timestamp: Option<i64>) -> String {
+    timestamp
+        .and_then(|t| OffsetDateTime::from_unix_timestamp(t).ok())
+        .and_then(|t| t.format(&Rfc3339).ok())
+        .unwrap_or_else(|| "-".to_string())
+}
+
+pub fn draw(frame: &mut Frame, app: &App) {
+    let [input_area, body_area, status_area] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(3),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    let [results_area, detail_area] =
+        Layout::horizontal([Constraint::Percentage(60), Constraint::Percentage(40)])
+            .areas(body_area);
+
+    // Refinements are shown apart, they aren't part of what is typed
+    let mut title = format!("Search ({})", app.field.name());
+    for refinement in &app.refinements {
+        title.push(' ');
+        title.push_str(refinement);
+    }
+    frame.render_widget(
+        Paragraph::new(app.query.as_str()).block(Block::bordered().title(title)),
+        input_area,
+    );
+    frame.set_cursor_position((
+        input_area.x + 1 + app.query.chars().count() as u16,
+        input_area.y + 1,
+    ));
+
+    // Only the visible window is turned into list items, results can be millions long
+    let height = results_area.height.saturating_sub(2) as usize;
+    let offset = (app.selected + 1).saturating_sub(height);
+    let items = app
+        .results
+        .iter()
+        .enumerate()
+        .skip(offset)
+        .take(height)
+        .map(|(i, (_, path))| {
+            let item = match app.usage.get(i) {
+                Some(usage) => ListItem::new(format!(
+                    "{:>10}  {}",
+                    human_size(usage.size),
+                    path.to_string_lossy()
+                )),
+                None => ListItem::new(path.to_string_lossy()),
+            };
+            if i == app.selected {
+                item.style(Style::new().add_modifier(Modifier::REVERSED))
+            } else {
+                item
+            }
+        })
+        .collect::<Vec<_>>();
+    frame.render_widget(
+        List::new(items).block(Block::bordered().title("Results")),
+        results_area,
+    );
+
+    let mut detail = match app.selection() {
+        Some((file, path)) => vec![
+            Line::from(format!("Name     : {}", file.name)),
+            Line::from(format!("Path     : {}", path.to_string_lossy())),
+            Line::from(format!("Kind     : {}", file.kind.as_str())),
+            Line::from(format!("Size     : {}", human_size(file.size))),
+            Line::from(format!("Created  : {}", format_timestamp(file.created))),
+            Line::from(format!("Modified : {}", format_timestamp(file.modified))),
+            Line::from(format!("Accessed : {}", format_timestamp(file.accessed))),
+        ],
+        None => vec![],
+    };
+    if let Some(usage) = app.usage.get(app.selected) {
+        detail.push(Line::from(format!(
+            "Total    : {}, {} files, {} dirs",
+            human_size(usage.size),
+            usage.files,
+            usage.directories
+        )));
+    }
+    frame.render_widget(
+        Paragraph::new(detail)
+            .wrap(Wrap { trim: false })
+            .block(Block::bordered().title("Details")),
+        detail_area,
+    );
+
+    let status = if let Some(message) = &app.message {
+        message.clone()
+    } else if !app.matcher.pending().is_empty() {
+        let pending = app.matcher.pending().iter().map(|key| key.to_string());
+        format!("{} ...", pending.collect::<Vec<_>>().join(" "))
+    } else if let Some(progress) = &app.rebuilding {
+        format!("rebuilding the index ... {progress}")
+    } else if app.searching {
+        "searching ...".to_string()
+    } else {
+        format!("{} results | {}", app.results.len(), app.hints())
+    };
+    frame.render_widget(Paragraph::new(status), status_area);
+}